@@ -0,0 +1,10 @@
+#![no_main]
+
+use advent_of_code::intcode::fuzz_bytes;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Err(violation) = fuzz_bytes(data) {
+        panic!("{violation}");
+    }
+});
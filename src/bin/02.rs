@@ -1,16 +1,12 @@
-use advent_of_code::{execute, parse_machine};
-
-use itertools::Itertools;
+use advent_of_code::{execute, intcode::NounVerbSearch, parse_machine, IntcodeMachine};
 
 advent_of_code::solution!(2);
 
 pub fn part_one(input: &str) -> Option<i64> {
-    let (_, mut machine) = parse_machine(input).unwrap();
-
-    machine.program[1] = 12;
-    machine.program[2] = 2;
+    let (_, machine) = parse_machine(input).unwrap();
+    let mut machine = machine.with_patches(&[(1, 12), (2, 2)]).ok()?;
 
-    execute(&mut machine);
+    execute(&mut machine).ok()?;
 
     Some(machine.program[0])
 }
@@ -19,20 +15,11 @@ pub fn part_two(input: &str) -> Option<usize> {
     let (_, machine) = parse_machine(input).unwrap();
 
     // can't run off the end of the program
-    let cap = machine.program.len() - 1;
-    for (noun, verb) in (0..=cap).cartesian_product(0..=cap) {
-        let mut trial = machine.clone();
-        trial.program[1] = noun.try_into().unwrap();
-        trial.program[2] = verb.try_into().unwrap();
-
-        execute(&mut trial);
-
-        if trial.program[0] == 19690720 {
-            return Some(100 * noun + verb);
-        }
-    }
+    let cap = machine.program.len() as i64 - 1;
+    let search = NounVerbSearch::new(0..=cap, |machine: &IntcodeMachine| machine.program[0]);
 
-    None
+    let (noun, verb) = search.find(&machine, 19690720)?;
+    Some((100 * noun + verb) as usize)
 }
 
 #[cfg(test)]
@@ -45,7 +32,7 @@ mod tests {
         let (_, mut machine) =
             parse_machine(&advent_of_code::template::read_file("examples", DAY)).unwrap();
 
-        execute(&mut machine);
+        execute(&mut machine).unwrap();
 
         assert_eq!(Some(machine.program[0]), Some(3500));
     }
@@ -6,8 +6,7 @@ pub fn part_one(input: &str) -> Option<i64> {
     let (_, mut machine) = parse_machine(input).unwrap();
 
     machine.inputs.push_back(1);
-    execute(&mut machine);
-    dbg!(&machine.outputs);
+    execute(&mut machine).ok()?;
 
     // last value is the diagnostic code
     machine.outputs.pop_back()
@@ -17,8 +16,24 @@ pub fn part_two(input: &str) -> Option<i64> {
     let (_, mut machine) = parse_machine(input).unwrap();
 
     machine.inputs.push_back(5);
-    execute(&mut machine);
+    execute(&mut machine).ok()?;
     machine.outputs.pop_front()
 }
 
-// no tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the example only compares its input to 8, so both parts see a value below it
+    #[test]
+    fn test_part_one() {
+        let result = part_one(&advent_of_code::template::read_file("examples", DAY));
+        assert_eq!(result, Some(999));
+    }
+
+    #[test]
+    fn test_part_two() {
+        let result = part_two(&advent_of_code::template::read_file("examples", DAY));
+        assert_eq!(result, Some(999));
+    }
+}
@@ -1,79 +1,59 @@
-use std::{cmp::max, collections::VecDeque, iter};
-
-use advent_of_code::{execute, parse_machine, IntcodeMachine, State};
+use advent_of_code::{
+    intcode::{run_batch, Outcome, Topology},
+    parse_machine, IntcodeMachine,
+};
 
 use itertools::Itertools;
 
 advent_of_code::solution!(7);
 
+/// Wires up an amplifier per phase setting in series, optionally looping the last one
+/// back around to the first, and returns the last signal sent to the thrusters.
+fn amplify(machine: &IntcodeMachine, phase_settings: &[i64], feedback: bool) -> Option<i64> {
+    let mut amps = Topology::new();
+    for phase_setting in phase_settings {
+        let amp = amps.add(machine.fork());
+        amps.seed(amp, [*phase_setting]);
+        if amp > 0 {
+            amps.connect(amp - 1, amp);
+        }
+    }
+    let last = phase_settings.len() - 1;
+    if feedback {
+        amps.connect(last, 0);
+    }
+    amps.seed(0, [0]);
+
+    match amps.run().ok()? {
+        Outcome::Halted => amps.outputs(last).last().copied(),
+        _ => None,
+    }
+}
+
 pub fn part_one(input: &str) -> Option<i64> {
     let (_, machine) = parse_machine(input).unwrap();
 
-    let mut maximum = 0;
     let combinations = (0..=4).permutations(5).collect::<Vec<_>>();
-    for combination in combinations {
-        let mut io = 0;
-        for phase_setting in combination.iter() {
-            let mut amp = machine.clone();
-            amp.inputs.push_back(*phase_setting);
-            amp.inputs.push_back(io);
-
-            execute(&mut amp);
-
-            io = amp.outputs.pop_front().unwrap();
-        }
-        // input to the thruster
-        maximum = max(maximum, io);
-    }
-
-    Some(maximum)
+    run_batch(&machine, &combinations, |machine, combination| {
+        amplify(&machine, combination, false)
+    })
+    .into_iter()
+    .collect::<Option<Vec<_>>>()?
+    .into_iter()
+    .max()
 }
 
 pub fn part_two(input: &str) -> Option<i64> {
     let (_, machine) = parse_machine(input).unwrap();
 
-    let mut maximum = 0;
     let combinations = (5..=9).permutations(5).collect::<Vec<_>>();
-    // let combinations = vec![vec![9, 8, 7, 6, 5]];
-    for combination in combinations {
-        let mut io: VecDeque<i64> = VecDeque::from([0]);
-        let mut machines: VecDeque<IntcodeMachine> =
-            iter::repeat(machine.clone()).take(5).collect();
-
-        // initialize each with the phase settings
-        for phase_setting in combination.iter() {
-            let mut amp = machines.pop_front().unwrap();
-            amp.inputs.push_back(*phase_setting);
-            machines.push_back(amp);
-        }
-
-        // and then just let it run
-        let mut terminated = 0;
-        let mut done;
-
-        'outer: loop {
-            // organizational loop that's not actually needed
-            for _ in 'A'..='E' {
-                let mut amp = machines.pop_front().unwrap();
-                amp.inputs.extend(io.drain(..));
-
-                done = execute(&mut amp);
-                io.extend(amp.outputs.drain(..));
-                machines.push_back(amp);
-
-                if matches!(done, State::Terminated) {
-                    terminated += 1;
-                    if terminated == 5 {
-                        break 'outer;
-                    }
-                }
-            }
-        }
-        // input to the thruster
-        maximum = max(maximum, io.pop_front().unwrap());
-    }
-
-    Some(maximum)
+    run_batch(&machine, &combinations, |machine, combination| {
+        amplify(&machine, combination, true)
+    })
+    .into_iter()
+    .collect::<Option<Vec<_>>>()?
+    .into_iter()
+    .max()
 }
 
 #[cfg(test)]
@@ -1,5 +1,4 @@
 use advent_of_code::{execute, parse_machine};
-use itertools::Itertools;
 
 advent_of_code::solution!(9);
 
@@ -7,7 +6,7 @@ pub fn part_one(input: &str) -> Option<i64> {
     let (_, mut machine) = parse_machine(input).unwrap();
 
     machine.inputs.push_back(1);
-    execute(&mut machine);
+    execute(&mut machine).ok()?;
 
     Some(machine.outputs.pop_front().unwrap())
 }
@@ -16,9 +15,7 @@ pub fn part_two(input: &str) -> Option<i64> {
     let (_, mut machine) = parse_machine(input).unwrap();
 
     machine.inputs.push_back(2);
-    execute(&mut machine);
-
-    println!("{}", machine.outputs.iter().join(","));
+    execute(&mut machine).ok()?;
 
     Some(machine.outputs.pop_front().unwrap())
 }
@@ -50,5 +47,12 @@ mod tests {
         assert_eq!(result, Some(1125899906842624));
     }
 
-    // no part 2 tests
+    #[test]
+    fn test_part_two() {
+        // none of the examples read their input
+        let result = part_two(&advent_of_code::template::read_file_part(
+            "examples", DAY, 1,
+        ));
+        assert_eq!(result, Some(1219070632396864));
+    }
 }
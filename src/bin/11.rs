@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use advent_of_code::{execute, parse_machine, Coordinate, State};
+use advent_of_code::{execute_with_io, parse_machine, Coordinate, IntcodeIo};
 use itertools::Itertools;
 
 advent_of_code::solution!(11);
@@ -58,6 +58,45 @@ fn update_robot(robot: &Robot, direction: i64) -> Robot {
     Robot { facing, location }
 }
 
+/// Steers the robot from the painting program's outputs, which come in pairs of color to
+/// paint and direction to turn, and feeds it the color of the panel it's standing on.
+struct Controller {
+    panel: HashMap<Coordinate<i32>, i64>,
+    robot: Robot,
+    color: Option<i64>,
+}
+
+impl IntcodeIo for Controller {
+    fn input(&mut self) -> Option<i64> {
+        Some(*self.panel.get(&self.robot.location).unwrap_or(&0))
+    }
+
+    fn output(&mut self, value: i64) {
+        match self.color.take() {
+            None => self.color = Some(value),
+            Some(color) => {
+                self.panel.insert(self.robot.location, color);
+                self.robot = update_robot(&self.robot, value);
+            }
+        }
+    }
+}
+
+fn run(input: &str, panel: HashMap<Coordinate<i32>, i64>) -> Option<HashMap<Coordinate<i32>, i64>> {
+    let (_, mut machine) = parse_machine(input).unwrap();
+    let mut controller = Controller {
+        panel,
+        robot: Robot {
+            facing: Facing::Up,
+            location: Coordinate { left: 0, top: 0 },
+        },
+        color: None,
+    };
+
+    execute_with_io(&mut machine, &mut controller).ok()?;
+    Some(controller.panel)
+}
+
 fn paint(panel: &HashMap<Coordinate<i32>, i64>) {
     let (min_x, max_x) = panel.keys().map(|l| l.left).minmax().into_option().unwrap();
     let (min_y, max_y) = panel.keys().map(|l| l.top).minmax().into_option().unwrap();
@@ -79,45 +118,13 @@ fn paint(panel: &HashMap<Coordinate<i32>, i64>) {
 }
 
 pub fn part_one(input: &str) -> Option<usize> {
-    let (_, mut machine) = parse_machine(input).unwrap();
-
-    let mut panel = HashMap::new();
-    let mut robot = Robot {
-        facing: Facing::Up,
-        location: Coordinate { left: 0, top: 0 },
-    };
-
-    while matches!(execute(&mut machine), State::WaitingForInput) {
-        for (color, direction) in machine.outputs.drain(..).tuple_windows() {
-            panel.insert(robot.location, color);
-            robot = update_robot(&robot, direction);
-        }
-        machine
-            .inputs
-            .push_back(*panel.get(&robot.location).unwrap_or(&0_i64));
-    }
+    let panel = run(input, HashMap::new())?;
 
     Some(panel.len())
 }
 
 pub fn part_two(input: &str) -> Option<u32> {
-    let (_, mut machine) = parse_machine(input).unwrap();
-
-    let mut panel = HashMap::from([(Coordinate { left: 0, top: 0 }, 1)]);
-    let mut robot = Robot {
-        facing: Facing::Up,
-        location: Coordinate { left: 0, top: 0 },
-    };
-
-    while matches!(execute(&mut machine), State::WaitingForInput) {
-        for (color, direction) in machine.outputs.drain(..).tuple_windows() {
-            panel.insert(robot.location, color);
-            robot = update_robot(&robot, direction);
-        }
-        machine
-            .inputs
-            .push_back(*panel.get(&robot.location).unwrap_or(&0_i64));
-    }
+    let panel = run(input, HashMap::from([(Coordinate { left: 0, top: 0 }, 1)]))?;
 
     paint(&panel);
     None
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use advent_of_code::{execute, parse_machine, Coordinate, State};
+use advent_of_code::{execute, execute_with_io, parse_machine, Coordinate, IntcodeIo};
 use itertools::Itertools;
 
 advent_of_code::solution!(13);
@@ -46,7 +46,7 @@ fn render(screen: &HashMap<Coordinate<i64>, i64>) {
 pub fn part_one(input: &str) -> Option<usize> {
     let (_, mut machine) = parse_machine(input).unwrap();
 
-    execute(&mut machine);
+    execute(&mut machine).ok()?;
 
     let mut screen = HashMap::new();
     for (x, y, id) in machine.outputs.iter().tuples() {
@@ -59,39 +59,61 @@ pub fn part_one(input: &str) -> Option<usize> {
     Some(screen.values().filter(|c| **c == 2).count())
 }
 
+/// Keeps the screen up to date from the game's (x, y, tile) outputs and moves the
+/// joystick to keep the paddle under the ball.
+struct Player {
+    screen: HashMap<Coordinate<i64>, i64>,
+    pending: Vec<i64>,
+}
+
+impl IntcodeIo for Player {
+    fn input(&mut self) -> Option<i64> {
+        let ball_x = self.screen.iter().find(|(_, v)| **v == 4).unwrap().0.left;
+        let paddle_x = self.screen.iter().find(|(_, v)| **v == 3).unwrap().0.left;
+
+        Some(match ball_x - paddle_x {
+            x if x < 0 => -1,
+            x if x > 0 => 1,
+            _ => 0,
+        })
+    }
+
+    fn output(&mut self, value: i64) {
+        self.pending.push(value);
+        if let [x, y, id] = self.pending[..] {
+            self.screen.insert(Coordinate { left: x, top: y }, id);
+            self.pending.clear();
+        }
+    }
+}
+
 pub fn part_two(input: &str) -> Option<i64> {
     let (_, mut machine) = parse_machine(input).unwrap();
-    let mut screen = HashMap::new();
+    let mut player = Player {
+        screen: HashMap::new(),
+        pending: Vec::new(),
+    };
 
     machine.program[0] = 2;
-    loop {
-        // will either be waiting on input or terminated
-        let state = execute(&mut machine);
-        // but we need to update the screen either way
-        for (x, y, id) in machine.outputs.iter().tuples() {
-            screen
-                .entry(Coordinate { left: *x, top: *y })
-                .and_modify(|k: &mut i64| *k = *id)
-                .or_insert(*id);
-        }
+    execute_with_io(&mut machine, &mut player).ok()?;
 
-        // then exit if it's done
-        if matches!(state, State::Terminated) {
-            break;
-        }
+    Some(*player.screen.get(&Coordinate { left: -1, top: 0 }).unwrap())
+}
 
-        // keep the paddle under the ball
-        let ball_x = screen.iter().find(|(_, v)| **v == 4).unwrap().0.left;
-        let paddle_x = screen.iter().find(|(_, v)| **v == 3).unwrap().0.left;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        machine.inputs.push_back(match ball_x - paddle_x {
-            x if x < 0 => -1,
-            x if x > 0 => 1,
-            _ => 0,
-        });
+    // draws a paddle, a ball and a block, then scores whatever the joystick reads
+    #[test]
+    fn test_part_one() {
+        let result = part_one(&advent_of_code::template::read_file("examples", DAY));
+        assert_eq!(result, Some(1));
     }
 
-    Some(*screen.get(&Coordinate { left: -1, top: 0 }).unwrap())
+    #[test]
+    fn test_part_two() {
+        let result = part_two(&advent_of_code::template::read_file("examples", DAY));
+        assert_eq!(result, Some(1));
+    }
 }
-
-// no tests
@@ -74,7 +74,7 @@ fn visualize(map: &Map) {
 }
 
 fn explore(machine: &mut IntcodeMachine, map: &mut Map, location: Location) -> u32 {
-    let mut stack = VecDeque::from([(machine.clone(), location, 0)]);
+    let mut stack = VecDeque::from([(machine.fork(), location, 0)]);
     let mut found = 0;
 
     while let Some((machine, location, steps)) = stack.pop_front() {
@@ -84,9 +84,9 @@ fn explore(machine: &mut IntcodeMachine, map: &mut Map, location: Location) -> u
                 continue;
             }
 
-            let mut branch_machine = machine.clone();
+            let mut branch_machine = machine.fork();
             branch_machine.inputs.push_back(to_command(&direction));
-            execute(&mut branch_machine);
+            execute(&mut branch_machine).expect("droid program faulted");
 
             let terrain = from_status_code(&branch_machine.outputs.pop_front().unwrap());
             map.insert(new_location, terrain);
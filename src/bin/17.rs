@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use advent_of_code::{execute, parse_machine, Coordinate};
+use advent_of_code::{parse_machine, AsciiMachine, Coordinate};
 
 advent_of_code::solution!(17);
 
@@ -81,13 +81,11 @@ fn parse_region(i: &str) -> Region {
 }
 
 pub fn part_one(input: &str) -> Option<usize> {
-    let (_, mut machine) = parse_machine(input).unwrap();
-
-    execute(&mut machine);
+    let (_, machine) = parse_machine(input).unwrap();
+    let mut ascii = AsciiMachine::new(machine);
 
-    let chars: String = machine.outputs.iter().map(|c| (*c as u8) as char).collect();
-    // print!("{chars}");
-    let region = parse_region(&chars);
+    let screen = ascii.read_until_prompt().ok()?;
+    let region = parse_region(&screen);
 
     let intersections = region
         .iter()
@@ -109,20 +107,19 @@ pub fn part_two(input: &str) -> Option<i64> {
     machine.program[0] = 2;
 
     // hand solved in data/examples/17-solved.txt
-    let main = "A,B,A,C,B,C,A,B,A,C\n";
-    let a = "R,6,L,10,R,8,R,8\n";
-    let b = "R,12,L,8,L,10\n";
-    let c = "R,12,L,10,R,6,L,10\n";
-    let live = "n\n";
-
-    let routine = format!("{}{}{}{}{}", main, a, b, c, live);
-    machine
-        .inputs
-        .extend(routine.as_bytes().iter().map(|c| *c as i64));
-
-    execute(&mut machine);
+    let main = "A,B,A,C,B,C,A,B,A,C";
+    let a = "R,6,L,10,R,8,R,8";
+    let b = "R,12,L,8,L,10";
+    let c = "R,12,L,10,R,6,L,10";
+    let live = "n";
+
+    let mut ascii = AsciiMachine::new(machine);
+    for line in [main, a, b, c, live] {
+        ascii.send_line(line);
+    }
+    ascii.read_until_prompt().ok()?;
 
-    Some(*machine.outputs.back().unwrap())
+    ascii.answer()
 }
 
 // no tests (technically there are examples but they don't work on the same interface)
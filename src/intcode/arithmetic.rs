@@ -0,0 +1,287 @@
+use num::{BigInt, ToPrimitive, Zero};
+
+use super::{
+    decode_instruction, locate, Instruction, IntcodeError, IntcodeMachine, Mode, Opcode, State,
+};
+
+/// What a machine does when `ADD` or `MUL` overflows an `i64`. The same in debug and
+/// release builds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Arithmetic {
+    /// Fault with [`IntcodeError::ArithmeticOverflow`].
+    #[default]
+    Checked,
+    /// Wrap around in two's complement.
+    Wrapping,
+    /// Keep the exact result in memory with [`Memory::write_big`](super::Memory::write_big).
+    /// Such values can be added, multiplied and compared, but outputs, jump targets and
+    /// relative base adjustments still have to fit in an `i64`.
+    ///
+    /// Extension opcodes and anything else reading memory directly see the low 64 bits of
+    /// a value too big for an `i64`.
+    Arbitrary,
+}
+
+impl IntcodeMachine {
+    pub fn with_arithmetic(mut self, arithmetic: Arithmetic) -> Self {
+        self.arithmetic = arithmetic;
+        self
+    }
+}
+
+/// An instruction being executed at full precision.
+struct Wide<'a> {
+    machine: &'a mut IntcodeMachine,
+    address: usize,
+    raw: i64,
+    instruction: Instruction,
+    parameters: [BigInt; 3],
+}
+
+impl Wide<'_> {
+    fn overflow(&self) -> IntcodeError {
+        IntcodeError::ArithmeticOverflow {
+            address: self.address,
+            instruction: self.raw,
+            opcode: self.instruction.opcode,
+        }
+    }
+
+    fn small(&self, value: BigInt) -> Result<i64, IntcodeError> {
+        value.to_i64().ok_or_else(|| self.overflow())
+    }
+
+    fn position(&self, n: usize) -> Result<usize, IntcodeError> {
+        let parameter = self.parameters[n]
+            .to_i64()
+            .ok_or(IntcodeError::AddressOverflow {
+                address: self.address,
+                instruction: self.raw,
+            })?;
+        let mode = self.instruction.modes[n];
+        locate(self.machine, self.address, self.raw, mode, parameter)?.ok_or(
+            IntcodeError::ImmediateWrite {
+                address: self.address,
+                instruction: self.raw,
+                opcode: self.instruction.opcode,
+            },
+        )
+    }
+
+    fn value(&self, n: usize) -> Result<BigInt, IntcodeError> {
+        match self.instruction.modes[n] {
+            Mode::Immediate => Ok(self.parameters[n].clone()),
+            Mode::Position | Mode::Relative => Ok(self.machine.program.read_big(self.position(n)?)),
+        }
+    }
+
+    fn store(&mut self, n: usize, value: BigInt) -> Result<(), IntcodeError> {
+        let target = self.position(n)?;
+        self.machine.program.write_big(target, value).map_err(|e| {
+            IntcodeError::MemoryLimitExceeded {
+                address: self.address,
+                instruction: self.raw,
+                target,
+                limit: e.limit,
+            }
+        })
+    }
+}
+
+/// Like [`step`](super::step), reading every value at full precision. Machines under
+/// [`Arithmetic::Arbitrary`] go through here once memory holds a value too big for an
+/// `i64`, and for any instruction that would create one.
+pub(super) fn step_wide(machine: &mut IntcodeMachine) -> Result<State, IntcodeError> {
+    let address = machine.instruction_pointer;
+    // faults report the instruction as `step` does, by the cell's low 64 bits
+    let raw = machine
+        .program
+        .get(address)
+        .ok_or(IntcodeError::InstructionPointerOutOfBounds { address })?;
+    // only the opcode and mode digits matter, which the remainder keeps along with the sign
+    let digits = (machine.program.read_big(address) % BigInt::from(100_000))
+        .to_i64()
+        .unwrap_or(0);
+    let instruction = decode_instruction(address, &digits).map_err(|e| match e {
+        IntcodeError::UnknownOpcode { opcode, .. } => IntcodeError::UnknownOpcode {
+            address,
+            instruction: raw,
+            opcode,
+        },
+        IntcodeError::InvalidMode { mode, .. } => IntcodeError::InvalidMode {
+            address,
+            instruction: raw,
+            mode,
+        },
+        e => e,
+    })?;
+    let length = instruction.opcode.length();
+
+    let mut parameters = [BigInt::zero(), BigInt::zero(), BigInt::zero()];
+    for (n, parameter) in parameters.iter_mut().enumerate().take(length - 1) {
+        machine
+            .program
+            .get(address + 1 + n)
+            .ok_or(IntcodeError::TruncatedInstruction {
+                address,
+                instruction: raw,
+                opcode: instruction.opcode,
+            })?;
+        *parameter = machine.program.read_big(address + 1 + n);
+    }
+    let mut next = address + length;
+
+    let mut wide = Wide {
+        machine,
+        address,
+        raw,
+        instruction,
+        parameters,
+    };
+    match instruction.opcode {
+        Opcode::Addition => {
+            let value = wide.value(0)? + wide.value(1)?;
+            wide.store(2, value)?;
+        }
+        Opcode::Multiplication => {
+            let value = wide.value(0)? * wide.value(1)?;
+            wide.store(2, value)?;
+        }
+        Opcode::Input => {
+            let Some(input) = wide.machine.inputs.front().copied() else {
+                return Ok(State::WaitingForInput);
+            };
+            wide.store(0, BigInt::from(input))?;
+            wide.machine.inputs.pop_front();
+        }
+        Opcode::Output => {
+            let output = wide.small(wide.value(0)?)?;
+            wide.machine.outputs.push_back(output);
+        }
+        Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+            let zero = wide.value(0)?.is_zero();
+            let jump = if instruction.opcode == Opcode::JumpIfTrue {
+                !zero
+            } else {
+                zero
+            };
+            if jump {
+                let target = wide.small(wide.value(1)?)?;
+                next = target.try_into().map_err(|_| IntcodeError::InvalidJump {
+                    address,
+                    instruction: raw,
+                    target,
+                })?;
+            }
+        }
+        Opcode::LessThan => {
+            let value = (wide.value(0)? < wide.value(1)?) as i64;
+            wide.store(2, BigInt::from(value))?;
+        }
+        Opcode::Equals => {
+            let value = (wide.value(0)? == wide.value(1)?) as i64;
+            wide.store(2, BigInt::from(value))?;
+        }
+        Opcode::AdjustRelativeBase => {
+            let adjustment = wide.small(wide.value(0)?)?;
+            wide.machine.relative_base = wide.machine.relative_base.checked_add(adjustment).ok_or(
+                IntcodeError::AddressOverflow {
+                    address,
+                    instruction: raw,
+                },
+            )?;
+        }
+        Opcode::Halt => return Ok(State::Terminated),
+    }
+
+    wide.machine.instruction_pointer = next;
+    Ok(State::Active)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{execute, parse_machine, Jit, Stepper};
+
+    const OVERFLOW: &str = "1102,9223372036854775807,2,0,99";
+
+    fn run(program: &str, arithmetic: Arithmetic) -> (Result<State, IntcodeError>, IntcodeMachine) {
+        let (_, machine) = parse_machine(program).unwrap();
+        let mut machine = machine.with_arithmetic(arithmetic);
+        let mut compiled = machine.clone();
+
+        let result = execute(&mut machine);
+        assert_eq!(Jit::new(&compiled).execute(&mut compiled), result);
+        assert_eq!(compiled.program, machine.program);
+        assert_eq!(compiled.outputs, machine.outputs);
+        (result, machine)
+    }
+
+    #[test]
+    fn test_checked() {
+        let (result, machine) = run(OVERFLOW, Arithmetic::Checked);
+        assert_eq!(
+            result,
+            Err(IntcodeError::ArithmeticOverflow {
+                address: 0,
+                instruction: 1102,
+                opcode: Opcode::Multiplication
+            })
+        );
+        assert_eq!(machine.program[0], 1102);
+        assert_eq!(machine.instruction_pointer, 0);
+    }
+
+    #[test]
+    fn test_wrapping() {
+        let (result, machine) = run(OVERFLOW, Arithmetic::Wrapping);
+        assert_eq!(result, Ok(State::Terminated));
+        assert_eq!(machine.program[0], -2);
+
+        let (_, machine) = run("1101,9223372036854775807,1,0,99", Arithmetic::Wrapping);
+        assert_eq!(machine.program[0], i64::MIN);
+    }
+
+    #[test]
+    fn test_arbitrary() {
+        let (result, machine) = run(OVERFLOW, Arithmetic::Arbitrary);
+        assert_eq!(result, Ok(State::Terminated));
+        assert_eq!(machine.program.read_big(0), BigInt::from(i64::MAX) * 2);
+        assert_eq!(machine.program[0], -2);
+
+        // squares 2^40 and its negation, compares them, then adds them back to zero
+        let program = "2,30,30,30,1002,33,-1099511627776,32,7,32,30,31,1,30,32,30,\
+                       1101,0,0,32,4,30,99,0,0,0,0,0,0,0,1099511627776,0,0,1099511627776";
+        let (result, machine) = run(program, Arithmetic::Arbitrary);
+        assert_eq!(result, Ok(State::Terminated));
+        assert_eq!(machine.program[31], 1);
+        assert_eq!(machine.outputs, [0]);
+        assert!(!machine.program.has_wide_cells());
+
+        // a value too big for an `i64` can't leave the machine
+        let (result, machine) = run(
+            "1102,9223372036854775807,2,7,4,7,99,0",
+            Arithmetic::Arbitrary,
+        );
+        assert_eq!(
+            result,
+            Err(IntcodeError::ArithmeticOverflow {
+                address: 4,
+                instruction: 4,
+                opcode: Opcode::Output
+            })
+        );
+        assert!(machine.outputs.is_empty());
+
+        // overwrites the next instruction with 2^64 - 2, whose low 64 bits read as -2
+        let (result, _) = run("1102,9223372036854775807,2,4,0", Arithmetic::Arbitrary);
+        assert_eq!(
+            result,
+            Err(IntcodeError::UnknownOpcode {
+                address: 4,
+                instruction: -2,
+                opcode: 14
+            })
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use super::{execute, IntcodeError, IntcodeMachine, State};
+
+/// Wraps a machine running an ASCII program: one that reads lines of text and prints a
+/// screen of text, usually followed by a single number outside the ASCII range with the
+/// answer.
+#[derive(Debug, Clone)]
+pub struct AsciiMachine {
+    pub machine: IntcodeMachine,
+    state: State,
+    answer: Option<i64>,
+}
+
+impl AsciiMachine {
+    pub fn new(machine: IntcodeMachine) -> Self {
+        AsciiMachine {
+            machine,
+            state: State::Active,
+            answer: None,
+        }
+    }
+
+    /// Where the machine stopped the last time it ran.
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// The last output outside the ASCII range read off the screen so far.
+    pub fn answer(&self) -> Option<i64> {
+        self.answer
+    }
+
+    /// Queues a line of input, adding the newline.
+    pub fn send_line(&mut self, line: &str) {
+        self.machine
+            .inputs
+            .extend(line.bytes().chain([b'\n']).map(i64::from));
+    }
+
+    /// Takes everything printed so far as text. Outputs outside the ASCII range are left
+    /// out and kept as the [`answer`](AsciiMachine::answer) instead.
+    pub fn read_screen(&mut self) -> String {
+        let mut screen = String::new();
+        for value in self.machine.outputs.drain(..) {
+            match u8::try_from(value) {
+                Ok(byte) if byte.is_ascii() => screen.push(byte as char),
+                _ => self.answer = Some(value),
+            }
+        }
+        screen
+    }
+
+    /// Runs the machine until it asks for more input or halts, and reads the screen.
+    pub fn read_until_prompt(&mut self) -> Result<String, IntcodeError> {
+        self.state = execute(&mut self.machine)?;
+        Ok(self.read_screen())
+    }
+}
+
+impl From<IntcodeMachine> for AsciiMachine {
+    fn from(machine: IntcodeMachine) -> Self {
+        AsciiMachine::new(machine)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::assemble;
+
+    #[test]
+    fn test_conversation() {
+        // prints "?\n", then echoes one line back followed by its length
+        let program = assemble(
+            "
+            start:  OUT #63
+                    OUT #10
+            read:   IN [char]
+                    OUT [char]
+                    EQ [char], #10, [done]
+                    ADD [count], #1, [count]
+                    JF [done], #read
+                    ADD [count], #999, [count]
+                    OUT [count]
+                    HLT
+            char:   .data 0
+            done:   .data 0
+            count:  .data 0
+            ",
+        )
+        .unwrap();
+        let mut ascii = AsciiMachine::from(IntcodeMachine::from_program(program));
+
+        assert_eq!(ascii.read_until_prompt(), Ok("?\n".to_string()));
+        assert_eq!(ascii.state(), State::WaitingForInput);
+
+        ascii.send_line("hello");
+        assert_eq!(ascii.read_until_prompt(), Ok("hello\n".to_string()));
+        assert_eq!(ascii.state(), State::Terminated);
+        assert_eq!(ascii.answer(), Some(1005));
+    }
+}
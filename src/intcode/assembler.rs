@@ -0,0 +1,367 @@
+use enum_iterator::all;
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{alpha1, alphanumeric1, char, digit1, i64, space0},
+    combinator::{all_consuming, map, opt, recognize},
+    multi::{many0, many0_count, separated_list0},
+    sequence::{delimited, pair, preceded, terminated},
+    IResult,
+};
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+};
+
+use super::{Mode, Opcode};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssemblerError {
+    Syntax {
+        line: usize,
+        text: String,
+    },
+    UnknownMnemonic {
+        line: usize,
+        mnemonic: String,
+    },
+    OperandCount {
+        line: usize,
+        mnemonic: String,
+        expected: usize,
+        found: usize,
+    },
+    ImmediateWrite {
+        line: usize,
+        mnemonic: String,
+    },
+    UndefinedLabel {
+        line: usize,
+        label: String,
+    },
+    DuplicateLabel {
+        line: usize,
+        label: String,
+    },
+}
+
+impl Display for AssemblerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssemblerError::Syntax { line, text } => {
+                write!(f, "line {line}: could not parse \"{text}\"")
+            }
+            AssemblerError::UnknownMnemonic { line, mnemonic } => {
+                write!(f, "line {line}: unknown mnemonic {mnemonic}")
+            }
+            AssemblerError::OperandCount {
+                line,
+                mnemonic,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {line}: {mnemonic} takes {expected} operand(s), found {found}"
+            ),
+            AssemblerError::ImmediateWrite { line, mnemonic } => {
+                write!(
+                    f,
+                    "line {line}: {mnemonic} can't write to an immediate operand"
+                )
+            }
+            AssemblerError::UndefinedLabel { line, label } => {
+                write!(f, "line {line}: undefined label {label}")
+            }
+            AssemblerError::DuplicateLabel { line, label } => {
+                write!(f, "line {line}: label {label} is already defined")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AssemblerError {}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Number(i64),
+    Label(String),
+}
+
+#[derive(Debug, Clone)]
+struct Operand {
+    mode: Mode,
+    value: Value,
+}
+
+#[derive(Debug)]
+enum Statement {
+    Instruction {
+        mnemonic: String,
+        operands: Vec<Operand>,
+    },
+    Data(Vec<Value>),
+    Empty,
+}
+
+#[derive(Debug)]
+struct Line {
+    labels: Vec<String>,
+    statement: Statement,
+}
+
+fn identifier(i: &str) -> IResult<&str, &str> {
+    recognize(pair(
+        alt((alpha1, tag("_"))),
+        many0_count(alt((alphanumeric1, tag("_")))),
+    ))(i)
+}
+
+fn value(i: &str) -> IResult<&str, Value> {
+    alt((
+        map(i64, Value::Number),
+        map(identifier, |l| Value::Label(l.to_string())),
+    ))(i)
+}
+
+fn operand(i: &str) -> IResult<&str, Operand> {
+    alt((
+        map(preceded(char('#'), value), |value| Operand {
+            mode: Mode::Immediate,
+            value,
+        }),
+        map(
+            delimited(
+                terminated(char('['), space0),
+                value,
+                preceded(space0, char(']')),
+            ),
+            |value| Operand {
+                mode: Mode::Position,
+                value,
+            },
+        ),
+        map(preceded(tag("rb"), opt(i64)), |offset| Operand {
+            mode: Mode::Relative,
+            value: Value::Number(offset.unwrap_or(0)),
+        }),
+    ))(i)
+}
+
+fn list<'a, T>(
+    item: impl FnMut(&'a str) -> IResult<&'a str, T>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, Vec<T>> {
+    separated_list0(delimited(space0, char(','), space0), item)
+}
+
+fn statement(i: &str) -> IResult<&str, Statement> {
+    alt((
+        map(
+            preceded(pair(tag(".data"), space0), list(value)),
+            Statement::Data,
+        ),
+        map(
+            pair(terminated(identifier, space0), list(operand)),
+            |(mnemonic, operands)| Statement::Instruction {
+                mnemonic: mnemonic.to_uppercase(),
+                operands,
+            },
+        ),
+        map(space0, |_| Statement::Empty),
+    ))(i)
+}
+
+fn line(i: &str) -> IResult<&str, Line> {
+    // listings from the disassembler are prefixed with their address, which is redundant here
+    let (i, _) = opt(terminated(digit1, pair(char(':'), space0)))(i)?;
+    let (i, labels) = many0(terminated(identifier, pair(char(':'), space0)))(i)?;
+    let (i, statement) = terminated(statement, space0)(i)?;
+
+    Ok((
+        i,
+        Line {
+            labels: labels.into_iter().map(str::to_string).collect(),
+            statement,
+        },
+    ))
+}
+
+fn mode_digit(mode: Mode) -> i64 {
+    match mode {
+        Mode::Position => 0,
+        Mode::Immediate => 1,
+        Mode::Relative => 2,
+    }
+}
+
+/// Assembles a textual program into Intcode.
+///
+/// Each line holds optional `label:`s followed by either a mnemonic as produced by
+/// [`disassemble`](super::disassemble) or a `.data` directive with literal values. Operands
+/// are written `#5` (immediate), `[12]` (position) or `rb+3` (relative), and labels can be
+/// used in place of numbers in immediate, position and `.data` values. Everything after a
+/// `;` is a comment.
+pub fn assemble(source: &str) -> Result<Vec<i64>, AssemblerError> {
+    let mut lines = Vec::new();
+    let mut labels = HashMap::new();
+    let mut address = 0;
+
+    for (number, text) in source.lines().enumerate() {
+        let number = number + 1;
+        let code = text.split(';').next().unwrap().trim();
+        let (_, parsed) = all_consuming(line)(code).map_err(|_| AssemblerError::Syntax {
+            line: number,
+            text: text.to_string(),
+        })?;
+
+        for label in parsed.labels.iter() {
+            if labels.insert(label.clone(), address).is_some() {
+                return Err(AssemblerError::DuplicateLabel {
+                    line: number,
+                    label: label.clone(),
+                });
+            }
+        }
+        address += match &parsed.statement {
+            Statement::Instruction { operands, .. } => 1 + operands.len(),
+            Statement::Data(values) => values.len(),
+            Statement::Empty => 0,
+        };
+        lines.push((number, parsed.statement));
+    }
+
+    let resolve = |line: usize, value: &Value| match value {
+        Value::Number(n) => Ok(*n),
+        Value::Label(label) => labels
+            .get(label)
+            .map(|address: &usize| *address as i64)
+            .ok_or(AssemblerError::UndefinedLabel {
+                line,
+                label: label.clone(),
+            }),
+    };
+
+    let mut program = Vec::with_capacity(address);
+    for (line, statement) in lines {
+        match statement {
+            Statement::Instruction { mnemonic, operands } => {
+                let opcode = all::<Opcode>()
+                    .find(|opcode| opcode.mnemonic() == mnemonic)
+                    .ok_or(AssemblerError::UnknownMnemonic {
+                        line,
+                        mnemonic: mnemonic.clone(),
+                    })?;
+                if operands.len() != opcode.length() - 1 {
+                    return Err(AssemblerError::OperandCount {
+                        line,
+                        mnemonic,
+                        expected: opcode.length() - 1,
+                        found: operands.len(),
+                    });
+                }
+                if let Some(written) = opcode.written_parameter() {
+                    if matches!(operands[written - 1].mode, Mode::Immediate) {
+                        return Err(AssemblerError::ImmediateWrite { line, mnemonic });
+                    }
+                }
+
+                let modes = operands
+                    .iter()
+                    .zip([100, 1000, 10000])
+                    .map(|(operand, place)| mode_digit(operand.mode) * place)
+                    .sum::<i64>();
+                program.push(opcode.number() + modes);
+                for operand in operands.iter() {
+                    program.push(resolve(line, &operand.value)?);
+                }
+            }
+            Statement::Data(values) => {
+                for value in values.iter() {
+                    program.push(resolve(line, value)?);
+                }
+            }
+            Statement::Empty => (),
+        }
+    }
+
+    Ok(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{intcode::disassemble, parse_machine, template::read_file};
+
+    #[test]
+    fn test_assemble() {
+        let source = "
+            ; count down from 3
+                    ADD #3, #0, [counter]
+            loop:   OUT [counter]
+                    ADD [counter], #-1, [counter]
+                    JT [counter], #loop
+                    HLT
+            counter: .data 0
+        ";
+        assert_eq!(
+            assemble(source),
+            Ok(vec![
+                1101, 3, 0, 14, 4, 14, 1001, 14, -1, 14, 1005, 14, 4, 99, 0
+            ])
+        );
+    }
+
+    #[test]
+    fn test_relative_operands() {
+        assert_eq!(
+            assemble("ARB #1\nOUT rb-1\nIN rb\nIN rb+2\nHLT"),
+            Ok(vec![109, 1, 204, -1, 203, 0, 203, 2, 99])
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            assemble("ADD #1, #2"),
+            Err(AssemblerError::OperandCount {
+                line: 1,
+                mnemonic: "ADD".to_string(),
+                expected: 3,
+                found: 2
+            })
+        );
+        assert_eq!(
+            assemble("HLT\nIN #1"),
+            Err(AssemblerError::ImmediateWrite {
+                line: 2,
+                mnemonic: "IN".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("JT #1, #nowhere"),
+            Err(AssemblerError::UndefinedLabel {
+                line: 1,
+                label: "nowhere".to_string()
+            })
+        );
+        assert!(matches!(
+            assemble("NOP"),
+            Err(AssemblerError::UnknownMnemonic { .. })
+        ));
+        assert!(matches!(
+            assemble("ADD #1, #2, {3}"),
+            Err(AssemblerError::Syntax { .. })
+        ));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for day in [2, 5, 7, 9] {
+            let input = read_file("examples", crate::template::Day::new(day).unwrap());
+            let (_, machine) = parse_machine(&input).unwrap();
+            let program = machine.program.to_vec();
+            let listing = disassemble(&program);
+            assert_eq!(assemble(&listing), Ok(program), "day {day}");
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use std::time::{Duration, Instant};
+
+use super::{step, IntcodeError, IntcodeIo, IntcodeMachine, State};
+
+/// How many instructions run between checks of the clock.
+const CLOCK_INTERVAL: u64 = 1024;
+
+/// Which limit of a [`Budget`] ran out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Instructions,
+    Time,
+    Outputs,
+}
+
+/// Limits on a single run of a machine. Unset limits don't apply.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Budget {
+    pub max_instructions: Option<u64>,
+    pub max_time: Option<Duration>,
+    pub max_outputs: Option<usize>,
+}
+
+impl Budget {
+    pub fn unlimited() -> Self {
+        Budget::default()
+    }
+
+    pub fn with_max_instructions(mut self, count: u64) -> Self {
+        self.max_instructions = Some(count);
+        self
+    }
+
+    pub fn with_max_time(mut self, time: Duration) -> Self {
+        self.max_time = Some(time);
+        self
+    }
+
+    /// Stops the run once this many outputs are waiting to be read.
+    pub fn with_max_outputs(mut self, count: usize) -> Self {
+        self.max_outputs = Some(count);
+        self
+    }
+}
+
+/// Like [`execute`](super::execute), but gives up with [`State::BudgetExhausted`] once any
+/// limit of the budget is reached. The machine is left at the next instruction, so calling
+/// this again (or `execute`) picks up where it stopped with a fresh budget.
+pub fn execute_with_budget(
+    machine: &mut IntcodeMachine,
+    budget: &Budget,
+) -> Result<State, IntcodeError> {
+    run(machine, budget, None)
+}
+
+/// Like [`execute_with_io`](super::execute_with_io), but gives up once any limit of the
+/// budget is reached, as [`execute_with_budget`] does. Outputs handed to `io` during the
+/// run count towards the output limit.
+pub fn execute_with_budget_and_io(
+    machine: &mut IntcodeMachine,
+    budget: &Budget,
+    io: &mut impl IntcodeIo,
+) -> Result<State, IntcodeError> {
+    run(machine, budget, Some(io))
+}
+
+fn run(
+    machine: &mut IntcodeMachine,
+    budget: &Budget,
+    mut io: Option<&mut dyn IntcodeIo>,
+) -> Result<State, IntcodeError> {
+    let start = Instant::now();
+    let mut executed = 0;
+    let mut handed_out = 0;
+
+    loop {
+        if budget.max_instructions.is_some_and(|max| executed >= max) {
+            return Ok(State::BudgetExhausted(Limit::Instructions));
+        }
+        if budget
+            .max_outputs
+            .is_some_and(|max| machine.outputs.len() + handed_out >= max)
+        {
+            return Ok(State::BudgetExhausted(Limit::Outputs));
+        }
+        // reading the clock every instruction would dominate the run time
+        if executed % CLOCK_INTERVAL == 0
+            && budget.max_time.is_some_and(|max| start.elapsed() >= max)
+        {
+            return Ok(State::BudgetExhausted(Limit::Time));
+        }
+
+        let state = step(machine)?;
+        if let Some(io) = io.as_deref_mut() {
+            while let Some(value) = machine.outputs.pop_front() {
+                io.output(value);
+                handed_out += 1;
+            }
+        }
+
+        match state {
+            State::Active => executed += 1,
+            State::WaitingForInput => match io.as_deref_mut().and_then(|io| io.input()) {
+                Some(value) => machine.inputs.push_back(value),
+                None => return Ok(state),
+            },
+            state => return Ok(state),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{execute, parse_machine, QueueIo};
+
+    #[test]
+    fn test_instruction_budget() {
+        // count to 10, then halt
+        let (_, mut machine) =
+            parse_machine("1001,13,1,13,1007,13,10,14,1005,14,0,99,0,0,0").unwrap();
+        let budget = Budget::unlimited().with_max_instructions(5);
+
+        assert_eq!(
+            execute_with_budget(&mut machine, &budget),
+            Ok(State::BudgetExhausted(Limit::Instructions))
+        );
+        assert_eq!(machine.instruction_pointer, 8);
+        assert_eq!(machine.program[13], 2);
+
+        assert_eq!(execute(&mut machine), Ok(State::Terminated));
+        assert_eq!(machine.program[13], 10);
+    }
+
+    #[test]
+    fn test_spinning_program() {
+        let (_, mut machine) = parse_machine("1105,1,0").unwrap();
+        let budget = Budget::unlimited().with_max_time(Duration::from_millis(10));
+        assert_eq!(
+            execute_with_budget(&mut machine, &budget),
+            Ok(State::BudgetExhausted(Limit::Time))
+        );
+    }
+
+    #[test]
+    fn test_output_budget() {
+        let (_, mut machine) = parse_machine("104,1,1105,1,0").unwrap();
+        let budget = Budget::unlimited().with_max_outputs(3);
+        assert_eq!(
+            execute_with_budget(&mut machine, &budget),
+            Ok(State::BudgetExhausted(Limit::Outputs))
+        );
+        assert_eq!(machine.outputs, [1, 1, 1]);
+
+        machine.outputs.clear();
+        assert_eq!(
+            execute_with_budget(&mut machine, &budget),
+            Ok(State::BudgetExhausted(Limit::Outputs))
+        );
+        assert_eq!(machine.outputs, [1, 1, 1]);
+    }
+
+    #[test]
+    fn test_budget_with_io() {
+        // echoes inputs forever
+        let (_, mut machine) = parse_machine("3,9,4,9,1105,1,0,99,99,0").unwrap();
+        let mut io = QueueIo::new([1, 2, 3, 4]);
+
+        let budget = Budget::unlimited().with_max_outputs(3);
+        assert_eq!(
+            execute_with_budget_and_io(&mut machine, &budget, &mut io),
+            Ok(State::BudgetExhausted(Limit::Outputs))
+        );
+        assert_eq!(io.outputs, [1, 2, 3]);
+
+        let budget = Budget::unlimited().with_max_instructions(3);
+        assert_eq!(
+            execute_with_budget_and_io(&mut machine, &budget, &mut io),
+            Ok(State::BudgetExhausted(Limit::Instructions))
+        );
+        assert_eq!(io.outputs, [1, 2, 3, 4]);
+
+        assert_eq!(
+            execute_with_budget_and_io(&mut machine, &Budget::unlimited(), &mut io),
+            Ok(State::WaitingForInput)
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use petgraph::{
+    algo::tarjan_scc,
+    dot::{Config, Dot},
+    graph::{DiGraph, EdgeReference, NodeIndex},
+};
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::{self, Display},
+    ops::Range,
+};
+
+use super::{decode_at, disassemble_instruction, reachable_instructions, successors, Mode, Opcode};
+
+/// A straight run of instructions that's only entered at the top and only branches at the
+/// bottom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    /// Addresses of the instructions in the block, in order.
+    pub instructions: Vec<usize>,
+    /// Address just past the last instruction.
+    pub end: usize,
+    /// Whether the block ends in a jump whose target is read from memory, which static
+    /// analysis can't follow.
+    pub indirect: bool,
+}
+
+impl BasicBlock {
+    pub fn start(&self) -> usize {
+        self.instructions[0]
+    }
+}
+
+impl Display for BasicBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}..{:04}", self.start(), self.end)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    FallThrough,
+    Jump,
+}
+
+impl Display for Edge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Edge::FallThrough => write!(f, "fall through"),
+            Edge::Jump => write!(f, "jump"),
+        }
+    }
+}
+
+/// An instruction writing into a cell that's part of a reachable instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodeWrite {
+    pub writer: usize,
+    pub target: usize,
+}
+
+/// Control-flow graph of the code reachable from a program's entry point, built the same
+/// way [`disassemble`](super::disassemble) finds code.
+#[derive(Debug, Clone)]
+pub struct ControlFlowGraph {
+    pub graph: DiGraph<BasicBlock, Edge>,
+    pub entry: Option<NodeIndex>,
+    /// Address ranges not covered by any reachable instruction.
+    pub unreachable: Vec<Range<usize>>,
+    /// Writes through position-mode operands that land inside reachable code. Writes
+    /// through the relative base can't be resolved statically and aren't included.
+    pub code_writes: Vec<CodeWrite>,
+    program: Vec<i64>,
+}
+
+fn is_branch(opcode: Opcode) -> bool {
+    matches!(
+        opcode,
+        Opcode::JumpIfTrue | Opcode::JumpIfFalse | Opcode::Halt
+    )
+}
+
+impl ControlFlowGraph {
+    pub fn new(program: &[i64]) -> Self {
+        let starts = reachable_instructions(program);
+        let instructions = (0..program.len())
+            .filter(|address| starts[*address])
+            .map(|address| (address, decode_at(program, address).unwrap().0))
+            .collect::<Vec<_>>();
+
+        // every jump target and everything following a branch starts a new block
+        let mut leaders = BTreeSet::from([0]);
+        for (address, instruction) in instructions.iter() {
+            let next = address + instruction.opcode.length();
+            for target in successors(program, *address) {
+                if target != next || is_branch(instruction.opcode) {
+                    leaders.insert(target);
+                }
+            }
+        }
+
+        let mut graph = DiGraph::new();
+        let mut blocks = HashMap::new();
+        let mut current: Option<BasicBlock> = None;
+        for (address, instruction) in instructions.iter() {
+            let contiguous = current.as_ref().is_some_and(|block| block.end == *address);
+            if leaders.contains(address) || !contiguous {
+                if let Some(block) = current.take() {
+                    blocks.insert(block.start(), graph.add_node(block));
+                }
+            }
+            let block = current.get_or_insert_with(|| BasicBlock {
+                instructions: Vec::new(),
+                end: *address,
+                indirect: false,
+            });
+            block.instructions.push(*address);
+            block.end = address + instruction.opcode.length();
+            block.indirect = matches!(instruction.opcode, Opcode::JumpIfTrue | Opcode::JumpIfFalse)
+                && !matches!(instruction.modes[1], Mode::Immediate);
+
+            if is_branch(instruction.opcode) {
+                blocks.insert(block.start(), graph.add_node(current.take().unwrap()));
+            }
+        }
+        if let Some(block) = current.take() {
+            blocks.insert(block.start(), graph.add_node(block));
+        }
+
+        for node in graph.node_indices().collect::<Vec<_>>() {
+            let block = &graph[node];
+            let (last, end) = (*block.instructions.last().unwrap(), block.end);
+            for target in successors(program, last) {
+                if let Some(to) = blocks.get(&target) {
+                    let edge = if target == end {
+                        Edge::FallThrough
+                    } else {
+                        Edge::Jump
+                    };
+                    graph.add_edge(node, *to, edge);
+                }
+            }
+        }
+
+        let mut covered = vec![false; program.len()];
+        for (address, instruction) in instructions.iter() {
+            covered[*address..address + instruction.opcode.length()].fill(true);
+        }
+        let mut unreachable: Vec<Range<usize>> = Vec::new();
+        for address in (0..program.len()).filter(|address| !covered[*address]) {
+            match unreachable.last_mut() {
+                Some(range) if range.end == address => range.end += 1,
+                _ => unreachable.push(address..address + 1),
+            }
+        }
+
+        let code_writes = instructions
+            .iter()
+            .filter_map(|(address, instruction)| {
+                let written = instruction.opcode.written_parameter()?;
+                if !matches!(instruction.modes[written - 1], Mode::Position) {
+                    return None;
+                }
+                let target = usize::try_from(program[address + written]).ok()?;
+                covered
+                    .get(target)
+                    .is_some_and(|code| *code)
+                    .then_some(CodeWrite {
+                        writer: *address,
+                        target,
+                    })
+            })
+            .collect();
+
+        ControlFlowGraph {
+            entry: blocks.get(&0).copied(),
+            graph,
+            unreachable,
+            code_writes,
+            program: program.to_vec(),
+        }
+    }
+
+    /// Groups of blocks that can loop back to themselves, each as the start addresses of
+    /// its blocks.
+    pub fn loops(&self) -> Vec<Vec<usize>> {
+        let mut loops = tarjan_scc(&self.graph)
+            .into_iter()
+            .filter(|component| {
+                component.len() > 1 || self.graph.contains_edge(component[0], component[0])
+            })
+            .map(|component| {
+                let mut starts = component
+                    .iter()
+                    .map(|node| self.graph[*node].start())
+                    .collect::<Vec<_>>();
+                starts.sort_unstable();
+                starts
+            })
+            .collect::<Vec<_>>();
+        loops.sort_unstable();
+        loops
+    }
+
+    /// Renders the graph in Graphviz DOT format. Blocks ending in an indirect jump are
+    /// drawn in red, blocks that get written to in orange, jumps as solid edges and fall
+    /// throughs as dashed ones. Unreachable ranges are listed in a separate grey node.
+    pub fn to_dot(&self) -> String {
+        let written = self
+            .code_writes
+            .iter()
+            .map(|write| write.target)
+            .collect::<BTreeSet<_>>();
+
+        let node_attributes = |_, (_, block): (NodeIndex, &BasicBlock)| {
+            let listing = block
+                .instructions
+                .iter()
+                .map(|address| {
+                    let (text, _) = disassemble_instruction(self.program.as_slice(), *address)
+                        .expect("blocks only hold valid instructions");
+                    format!("{address:04}: {text}\\l")
+                })
+                .collect::<String>();
+            let mut attributes = format!("shape=box, fontname=monospace, label=\"{listing}\"");
+            if block.indirect {
+                attributes.push_str(", color=red");
+            }
+            if written.range(block.start()..block.end).next().is_some() {
+                attributes.push_str(", style=filled, fillcolor=orange");
+            }
+            attributes
+        };
+        let edge_attributes = |_, edge: EdgeReference<Edge>| match edge.weight() {
+            Edge::FallThrough => "style=dashed".to_string(),
+            Edge::Jump => String::new(),
+        };
+
+        let dot = Dot::with_attr_getters(
+            &self.graph,
+            &[Config::NodeNoLabel, Config::EdgeNoLabel],
+            &edge_attributes,
+            &node_attributes,
+        );
+        let mut dot = format!("{dot:?}");
+
+        if !self.unreachable.is_empty() {
+            let ranges = self
+                .unreachable
+                .iter()
+                .map(|range| format!("{:04}..{:04}\\l", range.start, range.end))
+                .collect::<String>();
+            let closing = dot.rfind('}').unwrap();
+            dot.insert_str(
+                closing,
+                &format!(
+                    "    unreachable [ shape=note, color=grey, fontname=monospace, \
+                     label=\"unreachable\\l{ranges}\" ]\n"
+                ),
+            );
+        }
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocks() {
+        // 0: count [20] down from 3, 11: jump straight to 16, 14: dead, 16: halt
+        let program = [
+            1101, 3, 0, 20, 1001, 20, -1, 20, 1005, 20, 4, 1106, 0, 16, 99, 99, 99, 0, 0, 0, 0, 16,
+        ];
+        let cfg = ControlFlowGraph::new(&program);
+
+        let starts = cfg
+            .graph
+            .node_weights()
+            .map(BasicBlock::start)
+            .collect::<Vec<_>>();
+        assert_eq!(starts, [0, 4, 11, 16]);
+        assert_eq!(cfg.loops(), [vec![4]]);
+        assert_eq!(cfg.unreachable, [14..16, 17..22]);
+        assert!(cfg.code_writes.is_empty());
+
+        let dot = cfg.to_dot();
+        assert!(dot.contains("0004: ADD [20], #-1, [20]\\l0008: JT [20], #4\\l"));
+        assert!(dot.contains("unreachable\\l0014..0016\\l0017..0022\\l"));
+    }
+
+    #[test]
+    fn test_indirect_and_code_writes() {
+        // patches which cell its indirect jump reads its target from
+        let program = [1101, 0, 11, 9, 1105, 1, 7, 105, 1, 0, 99, 10];
+        let cfg = ControlFlowGraph::new(&program);
+
+        assert_eq!(
+            cfg.code_writes,
+            [CodeWrite {
+                writer: 0,
+                target: 9
+            }]
+        );
+        assert!(cfg.graph.node_weights().any(|block| block.indirect));
+        assert!(cfg.to_dot().contains("color=red"));
+    }
+}
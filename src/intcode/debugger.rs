@@ -0,0 +1,337 @@
+use itertools::Itertools;
+
+use std::{
+    collections::BTreeSet,
+    io::{self, BufRead, Write},
+};
+
+use super::{disassemble_instruction, step, IntcodeError, IntcodeMachine, State};
+
+/// Why the debugger handed control back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    Stepped,
+    Breakpoint(usize),
+    Watchpoint { address: usize, old: i64, new: i64 },
+    Halted(State),
+}
+
+/// Drives an [`IntcodeMachine`] one instruction at a time, stopping at breakpoints on
+/// addresses and at watchpoints whenever a watched cell changes value.
+#[derive(Debug, Clone)]
+pub struct Debugger {
+    pub machine: IntcodeMachine,
+    pub breakpoints: BTreeSet<usize>,
+    pub watchpoints: BTreeSet<usize>,
+}
+
+const HELP: &str = "\
+commands:
+  s, step [n]          execute n instructions (default 1)
+  c, continue          run until a breakpoint, watchpoint, halt or missing input
+  b, break <addr>      toggle a breakpoint
+  w, watch <addr>      toggle a watchpoint on a memory cell
+  r, regs              show ip, relative base and pending inputs/outputs
+  l, list [addr] [n]   disassemble n instructions from addr (default: ip, 5)
+  x <addr> [n]         show n memory cells from addr (default 1)
+  poke <addr> <value>  write a value to memory
+  in <values...>       queue input values
+  ascii <text>         queue a line of text as ASCII input
+  out                  drain and show pending outputs
+  save <file>          write the machine's state to a file
+  load <file>          replace the machine with one saved to a file
+  q, quit              leave the debugger";
+
+impl Debugger {
+    pub fn new(machine: IntcodeMachine) -> Self {
+        Debugger {
+            machine,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+        }
+    }
+
+    fn peek(&self, address: usize) -> i64 {
+        self.machine.program.read(address)
+    }
+
+    /// Executes a single instruction, ignoring breakpoints.
+    pub fn step(&mut self) -> Result<Stop, IntcodeError> {
+        let watched = self
+            .watchpoints
+            .iter()
+            .map(|address| (*address, self.peek(*address)))
+            .collect::<Vec<_>>();
+
+        let state = step(&mut self.machine)?;
+
+        for (address, old) in watched {
+            let new = self.peek(address);
+            if old != new {
+                return Ok(Stop::Watchpoint { address, old, new });
+            }
+        }
+        Ok(match state {
+            State::Active => Stop::Stepped,
+            _ => Stop::Halted(state),
+        })
+    }
+
+    /// Runs until a breakpoint or watchpoint is hit, or the machine stops on its own. A
+    /// breakpoint at the current instruction doesn't stop it from being executed.
+    pub fn resume(&mut self) -> Result<Stop, IntcodeError> {
+        loop {
+            match self.step()? {
+                Stop::Stepped => {
+                    let ip = self.machine.instruction_pointer;
+                    if self.breakpoints.contains(&ip) {
+                        return Ok(Stop::Breakpoint(ip));
+                    }
+                }
+                stop => return Ok(stop),
+            }
+        }
+    }
+
+    fn list(&self, output: &mut impl Write, from: usize, count: usize) -> io::Result<()> {
+        let mut address = from;
+        for _ in 0..count {
+            let marker = match (
+                address == self.machine.instruction_pointer,
+                self.breakpoints.contains(&address),
+            ) {
+                (true, true) => "*>",
+                (true, false) => " >",
+                (false, true) => "* ",
+                (false, false) => "  ",
+            };
+            match disassemble_instruction(&self.machine.program, address) {
+                Some((text, length)) => {
+                    writeln!(output, "{marker} {address:04}: {text}")?;
+                    address += length;
+                }
+                None => {
+                    writeln!(
+                        output,
+                        "{marker} {address:04}: .data {}",
+                        self.peek(address)
+                    )?;
+                    address += 1;
+                }
+            }
+            if address >= self.machine.program.len() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn registers(&self, output: &mut impl Write) -> io::Result<()> {
+        writeln!(
+            output,
+            "ip: {}  rb: {}\ninputs: [{}]\noutputs: [{}]",
+            self.machine.instruction_pointer,
+            self.machine.relative_base,
+            self.machine.inputs.iter().join(", "),
+            self.machine.outputs.iter().join(", "),
+        )
+    }
+
+    fn report(&self, output: &mut impl Write, stop: Result<Stop, IntcodeError>) -> io::Result<()> {
+        match stop {
+            Ok(Stop::Stepped) => (),
+            Ok(Stop::Breakpoint(address)) => writeln!(output, "breakpoint at {address}")?,
+            Ok(Stop::Watchpoint { address, old, new }) => {
+                writeln!(output, "watchpoint: [{address}] {old} -> {new}")?
+            }
+            Ok(Stop::Halted(State::WaitingForInput)) => writeln!(output, "waiting for input")?,
+            Ok(Stop::Halted(state)) => writeln!(output, "{state:?}")?,
+            Err(e) => writeln!(output, "fault: {e}")?,
+        }
+        self.list(output, self.machine.instruction_pointer, 1)
+    }
+
+    /// Executes a single debugger command. Returns `false` once the user asks to quit.
+    pub fn command(&mut self, line: &str, output: &mut impl Write) -> io::Result<bool> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(true);
+        };
+        let arguments = words.map(str::parse::<i64>).collect::<Result<Vec<_>, _>>();
+        let address = |n: usize| {
+            arguments
+                .as_ref()
+                .ok()
+                .and_then(|a| a.get(n))
+                .and_then(|a| usize::try_from(*a).ok())
+        };
+
+        match command {
+            "s" | "step" => {
+                let count = address(0).unwrap_or(1);
+                let mut stop = Ok(Stop::Stepped);
+                for _ in 0..count {
+                    stop = self.step();
+                    if !matches!(stop, Ok(Stop::Stepped)) {
+                        break;
+                    }
+                }
+                self.report(output, stop)?;
+            }
+            "c" | "continue" => {
+                let stop = self.resume();
+                self.report(output, stop)?;
+            }
+            "b" | "break" | "w" | "watch" => match address(0) {
+                Some(address) => {
+                    let (set, name) = match command {
+                        "b" | "break" => (&mut self.breakpoints, "breakpoint"),
+                        _ => (&mut self.watchpoints, "watchpoint"),
+                    };
+                    if set.remove(&address) {
+                        writeln!(output, "removed {name} at {address}")?;
+                    } else {
+                        set.insert(address);
+                        writeln!(output, "added {name} at {address}")?;
+                    }
+                }
+                None => writeln!(output, "usage: {command} <addr>")?,
+            },
+            "r" | "regs" => self.registers(output)?,
+            "l" | "list" => {
+                let from = address(0).unwrap_or(self.machine.instruction_pointer);
+                self.list(output, from, address(1).unwrap_or(5))?;
+            }
+            "x" => match address(0) {
+                Some(from) => {
+                    let count = address(1).unwrap_or(1);
+                    let values = (from..from + count).map(|a| self.peek(a)).join(", ");
+                    writeln!(output, "{from:04}: {values}")?;
+                }
+                None => writeln!(output, "usage: x <addr> [n]")?,
+            },
+            "poke" => match (address(0), arguments.as_ref().ok().and_then(|a| a.get(1))) {
+                (Some(address), Some(value)) => {
+                    if let Err(e) = self.machine.program.write(address, *value) {
+                        writeln!(output, "{address} is past the memory limit of {}", e.limit)?;
+                    }
+                }
+                _ => writeln!(output, "usage: poke <addr> <value>")?,
+            },
+            "in" => match &arguments {
+                Ok(values) if !values.is_empty() => self.machine.inputs.extend(values),
+                _ => writeln!(output, "usage: in <values...>")?,
+            },
+            "ascii" => {
+                let text = line.trim_start()[command.len()..].trim_start();
+                self.machine
+                    .inputs
+                    .extend(text.bytes().chain([b'\n']).map(i64::from));
+            }
+            "save" | "load" => {
+                let path = line.trim_start()[command.len()..].trim();
+                if path.is_empty() {
+                    writeln!(output, "usage: {command} <file>")?;
+                } else if command == "save" {
+                    if let Err(e) = self.machine.save(path) {
+                        writeln!(output, "could not save to {path}: {e}")?;
+                    }
+                } else {
+                    match IntcodeMachine::load(path) {
+                        Ok(machine) => {
+                            self.machine = machine;
+                            self.list(output, self.machine.instruction_pointer, 1)?;
+                        }
+                        Err(e) => writeln!(output, "could not load {path}: {e}")?,
+                    }
+                }
+            }
+            "out" => {
+                let values = self.machine.outputs.drain(..).join(", ");
+                writeln!(output, "{values}")?;
+            }
+            "q" | "quit" => return Ok(false),
+            "h" | "help" => writeln!(output, "{HELP}")?,
+            _ => writeln!(output, "unknown command {command}, try help")?,
+        }
+
+        Ok(true)
+    }
+
+    /// Reads commands from `input` until it's exhausted or the user quits.
+    pub fn repl(&mut self, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        self.list(&mut output, self.machine.instruction_pointer, 1)?;
+        write!(output, "(intcode) ")?;
+        output.flush()?;
+        for line in input.lines() {
+            if !self.command(&line?, &mut output)? {
+                break;
+            }
+            write!(output, "(intcode) ")?;
+            output.flush()?;
+        }
+        writeln!(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::assemble;
+
+    fn debugger(source: &str) -> Debugger {
+        let program = assemble(source).unwrap();
+        Debugger::new(IntcodeMachine::from_program(program))
+    }
+
+    #[test]
+    fn test_breakpoints_and_watchpoints() {
+        let mut debugger = debugger(
+            "
+                    ADD #2, #0, [counter]
+            loop:   ADD [counter], #-1, [counter]
+                    JT [counter], #loop
+                    HLT
+            counter: .data 0
+            ",
+        );
+        debugger.breakpoints.insert(11);
+        debugger.watchpoints.insert(12);
+
+        assert_eq!(
+            debugger.resume(),
+            Ok(Stop::Watchpoint {
+                address: 12,
+                old: 0,
+                new: 2
+            })
+        );
+        assert_eq!(
+            debugger.resume(),
+            Ok(Stop::Watchpoint {
+                address: 12,
+                old: 2,
+                new: 1
+            })
+        );
+        debugger.watchpoints.clear();
+        assert_eq!(debugger.resume(), Ok(Stop::Breakpoint(11)));
+        assert_eq!(debugger.resume(), Ok(Stop::Halted(State::Terminated)));
+    }
+
+    #[test]
+    fn test_commands() {
+        let mut debugger = debugger("IN [5]\nOUT [5]\nHLT");
+        let mut output = Vec::new();
+        debugger
+            .repl("s\nin 42\nc\nout\nx 5\nq\ns\n".as_bytes(), &mut output)
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains("waiting for input"));
+        assert!(output.contains("Terminated"));
+        assert!(output.contains("(intcode) 42\n"));
+        assert!(output.contains("0005: 42"));
+        assert_eq!(debugger.machine.instruction_pointer, 4);
+    }
+}
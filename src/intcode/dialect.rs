@@ -0,0 +1,390 @@
+use enum_iterator::all;
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Debug},
+    sync::Arc,
+};
+
+use super::{
+    locate, mode_from_digit, opcode_from_number, step, IntcodeError, IntcodeMachine, Mode, Opcode,
+    State, Stepper,
+};
+
+/// What an extension opcode does once its handler has run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    /// Move on to the next instruction.
+    Continue,
+    Jump(usize),
+    /// Halt with an exit code, leaving the instruction pointer on the instruction.
+    Exit(i64),
+    /// Stop and hand a code to the host. Executing again resumes after the instruction.
+    Trap(i64),
+}
+
+/// The parameters of an extension instruction, resolved through their modes the same way
+/// built-in instructions resolve theirs.
+pub struct Operands<'a> {
+    pub machine: &'a mut IntcodeMachine,
+    address: usize,
+    instruction: i64,
+    modes: Vec<Mode>,
+    parameters: Vec<i64>,
+}
+
+impl Operands<'_> {
+    /// Address of the instruction being executed.
+    pub fn address(&self) -> usize {
+        self.address
+    }
+
+    /// A fault blamed on the instruction being executed.
+    pub fn fault(&self, reason: impl Into<String>) -> IntcodeError {
+        IntcodeError::ExtensionFault {
+            address: self.address,
+            instruction: self.instruction,
+            reason: reason.into(),
+        }
+    }
+
+    fn target(&self, n: usize) -> Result<usize, IntcodeError> {
+        locate(
+            self.machine,
+            self.address,
+            self.instruction,
+            self.modes[n],
+            self.parameters[n],
+        )?
+        .ok_or_else(|| self.fault(format!("writes parameter {} in immediate mode", n + 1)))
+    }
+
+    /// The value of the `n`th parameter (0-indexed).
+    pub fn read(&self, n: usize) -> Result<i64, IntcodeError> {
+        match self.modes[n] {
+            Mode::Immediate => Ok(self.parameters[n]),
+            Mode::Position | Mode::Relative => Ok(self.machine.program.read(self.target(n)?)),
+        }
+    }
+
+    /// Stores a value where the `n`th parameter (0-indexed) points.
+    pub fn write(&mut self, n: usize, value: i64) -> Result<(), IntcodeError> {
+        let target = self.target(n)?;
+        self.machine
+            .program
+            .write(target, value)
+            .map_err(|e| IntcodeError::MemoryLimitExceeded {
+                address: self.address,
+                instruction: self.instruction,
+                target,
+                limit: e.limit,
+            })
+    }
+
+    /// Jumps to a computed target, faulting if it's negative.
+    pub fn jump(&self, target: i64) -> Result<Effect, IntcodeError> {
+        let target = target.try_into().map_err(|_| IntcodeError::InvalidJump {
+            address: self.address,
+            instruction: self.instruction,
+            target,
+        })?;
+        Ok(Effect::Jump(target))
+    }
+}
+
+type Handler = dyn Fn(&mut Operands) -> Result<Effect, IntcodeError> + Send + Sync;
+
+/// An opcode plugged into an [`InstructionSet`].
+#[derive(Clone)]
+pub struct Extension {
+    pub mnemonic: String,
+    /// Number of parameters following the opcode.
+    pub arity: usize,
+    handler: Arc<Handler>,
+}
+
+impl Debug for Extension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Extension")
+            .field("mnemonic", &self.mnemonic)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The opcodes and parameter modes a machine understands: a subset of the built-in ones,
+/// e.g. only those introduced by a given day, plus any extensions. Use its [`Stepper::execute`] in place
+/// of [`execute`](super::execute) to run a machine under it.
+#[derive(Debug, Clone)]
+pub struct InstructionSet {
+    builtins: Vec<Opcode>,
+    modes: Vec<Mode>,
+    extensions: HashMap<i64, Extension>,
+}
+
+impl Default for InstructionSet {
+    fn default() -> Self {
+        InstructionSet::full()
+    }
+}
+
+impl InstructionSet {
+    /// Every built-in opcode, as of day 9.
+    pub fn full() -> Self {
+        InstructionSet::only(&all::<Opcode>().collect::<Vec<_>>())
+    }
+
+    pub fn only(opcodes: &[Opcode]) -> Self {
+        InstructionSet {
+            builtins: opcodes.to_vec(),
+            modes: vec![Mode::Position, Mode::Immediate, Mode::Relative],
+            extensions: HashMap::new(),
+        }
+    }
+
+    /// The built-in opcodes and modes available as of the given day: day 2 introduces
+    /// `ADD`, `MUL` and `HLT` in position mode, day 5 input, output, jumps, comparisons
+    /// and immediate mode, and day 9 `ARB` and relative mode.
+    pub fn day(day: u32) -> Self {
+        let opcodes = all::<Opcode>().filter(|opcode| match opcode {
+            Opcode::Addition | Opcode::Multiplication | Opcode::Halt => day >= 2,
+            Opcode::AdjustRelativeBase => day >= 9,
+            _ => day >= 5,
+        });
+        let modes = [Mode::Position, Mode::Immediate, Mode::Relative]
+            .into_iter()
+            .filter(|mode| match mode {
+                Mode::Position => true,
+                Mode::Immediate => day >= 5,
+                Mode::Relative => day >= 9,
+            });
+        InstructionSet {
+            modes: modes.collect(),
+            ..InstructionSet::only(&opcodes.collect::<Vec<_>>())
+        }
+    }
+
+    /// Plugs in an opcode taking `arity` parameters. It takes precedence over a built-in
+    /// opcode with the same number.
+    pub fn with_opcode(
+        mut self,
+        number: i64,
+        mnemonic: &str,
+        arity: usize,
+        handler: impl Fn(&mut Operands) -> Result<Effect, IntcodeError> + Send + Sync + 'static,
+    ) -> Self {
+        self.extensions.insert(
+            number,
+            Extension {
+                mnemonic: mnemonic.to_string(),
+                arity,
+                handler: Arc::new(handler),
+            },
+        );
+        self
+    }
+
+    pub fn allows(&self, opcode: Opcode) -> bool {
+        self.builtins.contains(&opcode)
+    }
+
+    pub fn extension(&self, number: i64) -> Option<&Extension> {
+        self.extensions.get(&number)
+    }
+
+    /// Decodes the mode digit at `place` in `raw`, faulting on modes outside the set.
+    fn mode(&self, address: usize, raw: i64, place: i64) -> Result<Mode, IntcodeError> {
+        let digit = raw.checked_div(place).unwrap_or(0) % 10;
+        mode_from_digit(digit)
+            .filter(|mode| self.modes.contains(mode))
+            .ok_or(IntcodeError::InvalidMode {
+                address,
+                instruction: raw,
+                mode: digit,
+            })
+    }
+}
+
+impl Stepper for InstructionSet {
+    /// Executes a single instruction. Built-in opcodes outside the set fault as unknown.
+    fn step(&mut self, machine: &mut IntcodeMachine) -> Result<State, IntcodeError> {
+        let address = machine.instruction_pointer;
+        let raw = machine
+            .program
+            .get(address)
+            .ok_or(IntcodeError::InstructionPointerOutOfBounds { address })?;
+        let number = raw % 100;
+
+        let Some(extension) = self.extensions.get(&number) else {
+            let Some(opcode) = opcode_from_number(&number) else {
+                return step(machine);
+            };
+            if !self.allows(opcode) {
+                return Err(IntcodeError::UnknownOpcode {
+                    address,
+                    instruction: raw,
+                    opcode: number,
+                });
+            }
+            let mut place = 100;
+            for _ in 1..opcode.length() {
+                self.mode(address, raw, place)?;
+                place *= 10;
+            }
+            return step(machine);
+        };
+
+        // modes carry on past the usual three digits for longer instructions
+        let mut modes = Vec::with_capacity(extension.arity);
+        let mut place = 100;
+        for _ in 0..extension.arity {
+            modes.push(self.mode(address, raw, place)?);
+            place = place.saturating_mul(10);
+        }
+        let parameters = (0..extension.arity)
+            .map(|n| machine.program.get(address + 1 + n))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(IntcodeError::ExtensionFault {
+                address,
+                instruction: raw,
+                reason: format!("{} runs past the end of the program", extension.mnemonic),
+            })?;
+
+        let mut operands = Operands {
+            machine,
+            address,
+            instruction: raw,
+            modes,
+            parameters,
+        };
+        let effect = (extension.handler)(&mut operands)?;
+
+        let next = address + 1 + extension.arity;
+        match effect {
+            Effect::Continue => machine.instruction_pointer = next,
+            Effect::Jump(target) => machine.instruction_pointer = target,
+            Effect::Exit(code) => return Ok(State::Exited(code)),
+            Effect::Trap(code) => {
+                machine.instruction_pointer = next;
+                return Ok(State::Trapped(code));
+            }
+        }
+        Ok(State::Active)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::parse_machine;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_restricted() {
+        // reads an input, which day 2 knows nothing about
+        let (_, machine) = parse_machine("1,0,0,0,3,0,99").unwrap();
+
+        let mut day_two = machine.clone();
+        assert_eq!(
+            InstructionSet::day(2).execute(&mut day_two),
+            Err(IntcodeError::UnknownOpcode {
+                address: 4,
+                instruction: 3,
+                opcode: 3
+            })
+        );
+        assert_eq!(day_two.program[0], 2);
+
+        let mut day_five = machine;
+        assert_eq!(
+            InstructionSet::day(5).execute(&mut day_five),
+            Ok(State::WaitingForInput)
+        );
+        assert!(!InstructionSet::day(5).allows(Opcode::AdjustRelativeBase));
+    }
+
+    #[test]
+    fn test_restricted_modes() {
+        // adds two immediates, which day 2 only knows how to read by position
+        let (_, mut machine) = parse_machine("1101,1,1,0,99").unwrap();
+        assert_eq!(
+            InstructionSet::day(2).execute(&mut machine),
+            Err(IntcodeError::InvalidMode {
+                address: 0,
+                instruction: 1101,
+                mode: 1
+            })
+        );
+        assert_eq!(
+            InstructionSet::day(5).execute(&mut machine),
+            Ok(State::Terminated)
+        );
+        assert_eq!(machine.program[0], 2);
+
+        // outputs relative to the base, which day 9 introduces
+        let (_, machine) = parse_machine("204,0,99").unwrap();
+        assert_eq!(
+            InstructionSet::day(5).execute(&mut machine.clone()),
+            Err(IntcodeError::InvalidMode {
+                address: 0,
+                instruction: 204,
+                mode: 2
+            })
+        );
+        assert_eq!(
+            InstructionSet::day(9).execute(&mut machine.clone()),
+            Ok(State::Terminated)
+        );
+    }
+
+    #[test]
+    fn test_extensions() {
+        // prints [11], doubles it, prints it again, then prints -1
+        let (_, mut machine) = parse_machine("42,11,1002,11,2,11,42,11,142,-1,99,5").unwrap();
+        let printed = Arc::new(Mutex::new(Vec::new()));
+        let log = printed.clone();
+        let mut set = InstructionSet::full().with_opcode(42, "DBG", 1, move |operands| {
+            log.lock().unwrap().push(operands.read(0)?);
+            Ok(Effect::Continue)
+        });
+
+        assert_eq!(set.extension(42).unwrap().arity, 1);
+        assert_eq!(set.execute(&mut machine), Ok(State::Terminated));
+        assert_eq!(*printed.lock().unwrap(), [5, 10, -1]);
+    }
+
+    #[test]
+    fn test_exit_and_trap() {
+        // asks the host for a value, stores it in [9], then exits with it
+        let (_, mut machine) = parse_machine("50,1,51,9,20,9,99,0,0,0").unwrap();
+        let mut set = InstructionSet::full()
+            .with_opcode(50, "TRAP", 1, |operands| {
+                Ok(Effect::Trap(operands.read(0)?))
+            })
+            .with_opcode(51, "POP", 1, |operands| {
+                let value = operands.machine.inputs.pop_front();
+                let value = value.ok_or_else(|| operands.fault("found no value from the host"))?;
+                operands.write(0, value)?;
+                Ok(Effect::Continue)
+            })
+            .with_opcode(20, "EXIT", 1, |operands| {
+                Ok(Effect::Exit(operands.read(0)?))
+            });
+
+        assert_eq!(set.execute(&mut machine), Ok(State::Trapped(1)));
+        assert_eq!(machine.instruction_pointer, 2);
+        machine.inputs.push_back(12);
+        assert_eq!(set.execute(&mut machine), Ok(State::Exited(12)));
+        assert_eq!(machine.instruction_pointer, 4);
+
+        let (_, mut machine) = parse_machine("51,9").unwrap();
+        assert_eq!(
+            set.execute(&mut machine),
+            Err(IntcodeError::ExtensionFault {
+                address: 0,
+                instruction: 51,
+                reason: "found no value from the host".into()
+            })
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use std::fmt::Write;
+
+use super::{decode_instruction, Cells, Instruction, Mode, Opcode};
+
+/// How many data cells are listed per `.data` line.
+const DATA_PER_LINE: usize = 8;
+
+/// Decodes the instruction at `address` if it could actually be executed there: the opcode
+/// and modes are valid, all parameters are inside the program and nothing is written in
+/// immediate mode.
+pub(super) fn decode_at<C: Cells + ?Sized>(
+    program: &C,
+    address: usize,
+) -> Option<(Instruction, [i64; 3])> {
+    let raw = program.cell(address)?;
+    let instruction = decode_instruction(address, &raw).ok()?;
+    let mut parameters = [0; 3];
+    for (n, parameter) in parameters
+        .iter_mut()
+        .take(instruction.opcode.length() - 1)
+        .enumerate()
+    {
+        *parameter = program.cell(address + 1 + n)?;
+    }
+    if let Some(written) = instruction.opcode.written_parameter() {
+        if matches!(instruction.modes[written - 1], Mode::Immediate) {
+            return None;
+        }
+    }
+
+    Some((instruction, parameters))
+}
+
+/// Whether `raw` only carries mode digits for parameters the opcode actually has, i.e.
+/// whether the assembler would produce the same value from the mnemonic.
+fn is_canonical(raw: i64, opcode: Opcode) -> bool {
+    raw >= 0 && raw / 10_i64.pow(opcode.length() as u32 + 1) == 0
+}
+
+fn operand(mode: Mode, value: i64) -> String {
+    match mode {
+        Mode::Position => format!("[{value}]"),
+        Mode::Immediate => format!("#{value}"),
+        Mode::Relative if value < 0 => format!("rb{value}"),
+        Mode::Relative => format!("rb+{value}"),
+    }
+}
+
+fn render(instruction: &Instruction, parameters: &[i64; 3]) -> String {
+    let operands = instruction
+        .modes
+        .iter()
+        .zip(parameters)
+        .take(instruction.opcode.length() - 1)
+        .map(|(mode, value)| operand(*mode, *value))
+        .collect::<Vec<_>>();
+
+    if operands.is_empty() {
+        instruction.opcode.mnemonic().to_string()
+    } else {
+        format!("{} {}", instruction.opcode.mnemonic(), operands.join(", "))
+    }
+}
+
+/// Renders the single instruction at `address` as assembly, returning it along with the
+/// number of cells it occupies. Returns `None` if no valid instruction starts there.
+pub fn disassemble_instruction<C: Cells + ?Sized>(
+    program: &C,
+    address: usize,
+) -> Option<(String, usize)> {
+    let (instruction, parameters) = decode_at(program, address)?;
+    Some((
+        render(&instruction, &parameters),
+        instruction.opcode.length(),
+    ))
+}
+
+pub(super) fn successors(program: &[i64], address: usize) -> Vec<usize> {
+    let Some((instruction, parameters)) = decode_at(program, address) else {
+        return Vec::new();
+    };
+    let next = address + instruction.opcode.length();
+    let [mode_1, mode_2, _] = instruction.modes;
+
+    match instruction.opcode {
+        Opcode::Halt => Vec::new(),
+        Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+            let mut targets = Vec::new();
+            let taken = match mode_1 {
+                Mode::Immediate => {
+                    Some((parameters[0] != 0) == matches!(instruction.opcode, Opcode::JumpIfTrue))
+                }
+                _ => None,
+            };
+            if taken != Some(true) {
+                targets.push(next);
+            }
+            // indirect jumps can't be followed statically
+            if taken != Some(false) && matches!(mode_2, Mode::Immediate) {
+                if let Ok(target) = usize::try_from(parameters[1]) {
+                    targets.push(target);
+                }
+            }
+            targets
+        }
+        Opcode::Addition | Opcode::Multiplication => {
+            let mut targets = vec![next];
+            // calling convention: store the return address, then jump unconditionally
+            let identity = match instruction.opcode {
+                Opcode::Addition => 0,
+                _ => 1,
+            };
+            let constant = match (mode_1, mode_2) {
+                (Mode::Immediate, Mode::Immediate) if parameters[1] == identity => {
+                    Some(parameters[0])
+                }
+                (Mode::Immediate, Mode::Immediate) if parameters[0] == identity => {
+                    Some(parameters[1])
+                }
+                _ => None,
+            };
+            let jumps_after = successors(program, next).len() == 1
+                && decode_at(program, next).is_some_and(|(jump, _)| {
+                    matches!(jump.opcode, Opcode::JumpIfTrue | Opcode::JumpIfFalse)
+                });
+            if let Some(constant) = constant {
+                if jumps_after && usize::try_from(constant) == Ok(next + 3) {
+                    targets.push(next + 3);
+                }
+            }
+            targets
+        }
+        _ => vec![next],
+    }
+}
+
+/// Returns, for every address in the program, whether it starts an instruction reachable
+/// from the entry point. Direct jumps and the store-then-jump calling convention are
+/// followed; indirect jumps are not, so code only reached through them shows up as data.
+pub fn reachable_instructions(program: &[i64]) -> Vec<bool> {
+    let mut starts = vec![false; program.len()];
+    let mut pending = vec![0];
+
+    while let Some(address) = pending.pop() {
+        if address >= program.len() || starts[address] || decode_at(program, address).is_none() {
+            continue;
+        }
+        starts[address] = true;
+        pending.extend(successors(program, address));
+    }
+
+    starts
+}
+
+/// Produces an annotated listing of the program, one instruction per line. Operands are
+/// rendered as `[12]` (position), `#5` (immediate) and `rb+3` (relative); cells that aren't
+/// reachable as code are listed as `.data`.
+pub fn disassemble(program: &[i64]) -> String {
+    let starts = reachable_instructions(program);
+    let mut listing = String::new();
+    let mut data = Vec::new();
+    let mut address = 0;
+
+    macro_rules! flush_data {
+        () => {{
+            let mut data_address = address - data.len();
+            for chunk in data.chunks(DATA_PER_LINE) {
+                let values = chunk.iter().map(i64::to_string).collect::<Vec<_>>();
+                writeln!(listing, "{data_address:04}: .data {}", values.join(", ")).unwrap();
+                data_address += chunk.len();
+            }
+            data.clear();
+        }};
+    }
+
+    while address < program.len() {
+        if !starts[address] {
+            data.push(program[address]);
+            address += 1;
+            continue;
+        }
+        flush_data!();
+
+        let (instruction, parameters) = decode_at(program, address).unwrap();
+        let length = instruction.opcode.length();
+        let text = render(&instruction, &parameters);
+        if is_canonical(program[address], instruction.opcode) {
+            writeln!(listing, "{address:04}: {text}").unwrap();
+        } else {
+            // keep the stray mode digits so the listing still assembles to the same program
+            let values = program[address..address + length]
+                .iter()
+                .map(i64::to_string)
+                .collect::<Vec<_>>();
+            writeln!(
+                listing,
+                "{address:04}: .data {} ; {text}",
+                values.join(", ")
+            )
+            .unwrap();
+        }
+        address += length;
+    }
+    flush_data!();
+
+    listing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disassemble() {
+        let program = [1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+        assert_eq!(
+            disassemble(&program),
+            "0000: ADD [9], [10], [3]\n\
+             0004: MUL [3], [11], [0]\n\
+             0008: HLT\n\
+             0009: .data 30, 40, 50\n"
+        );
+    }
+
+    #[test]
+    fn test_relative_and_jumps() {
+        let program = [
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        assert_eq!(
+            disassemble(&program),
+            "0000: ARB #1\n\
+             0002: OUT rb-1\n\
+             0004: ADD [100], #1, [100]\n\
+             0008: EQ [100], #16, [101]\n\
+             0012: JF [101], #0\n\
+             0015: HLT\n"
+        );
+    }
+
+    #[test]
+    fn test_call_convention() {
+        // store the return address at rb+0, jump to 9, which returns through it
+        let program = [21101, 7, 0, 0, 1105, 1, 9, 99, 0, 2106, 0, 0];
+        let starts = reachable_instructions(&program);
+        assert!(starts[7]);
+        assert!(starts[9]);
+        assert!(!starts[8]);
+    }
+
+    #[test]
+    fn test_non_canonical() {
+        let program = [11104, 5, 99];
+        assert_eq!(
+            disassemble(&program),
+            "0000: .data 11104, 5 ; OUT #5\n\
+             0002: HLT\n"
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use std::fmt::{self, Display};
+
+use super::Opcode;
+
+/// Faults raised by the Intcode VM. Each variant carries the address of the faulting
+/// instruction and its raw value so a caller can report or recover from it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntcodeError {
+    UnknownOpcode {
+        address: usize,
+        instruction: i64,
+        opcode: i64,
+    },
+    InvalidMode {
+        address: usize,
+        instruction: i64,
+        mode: i64,
+    },
+    NegativeAddress {
+        address: usize,
+        instruction: i64,
+        target: i64,
+    },
+    AddressOverflow {
+        address: usize,
+        instruction: i64,
+    },
+    ImmediateWrite {
+        address: usize,
+        instruction: i64,
+        opcode: Opcode,
+    },
+    MemoryLimitExceeded {
+        address: usize,
+        instruction: i64,
+        target: usize,
+        limit: usize,
+    },
+    InvalidJump {
+        address: usize,
+        instruction: i64,
+        target: i64,
+    },
+    TruncatedInstruction {
+        address: usize,
+        instruction: i64,
+        opcode: Opcode,
+    },
+    InstructionPointerOutOfBounds {
+        address: usize,
+    },
+    /// A result didn't fit in an `i64` under [`Arithmetic::Checked`](super::Arithmetic), or
+    /// a value too big for one was used as an output, jump target or relative base.
+    ArithmeticOverflow {
+        address: usize,
+        instruction: i64,
+        opcode: Opcode,
+    },
+    /// Raised by or on behalf of an opcode plugged into an [`InstructionSet`](super::InstructionSet).
+    ExtensionFault {
+        address: usize,
+        instruction: i64,
+        reason: String,
+    },
+}
+
+impl Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntcodeError::UnknownOpcode {
+                address,
+                instruction,
+                opcode,
+            } => write!(
+                f,
+                "unknown opcode {opcode} in instruction {instruction} at address {address}"
+            ),
+            IntcodeError::InvalidMode {
+                address,
+                instruction,
+                mode,
+            } => write!(
+                f,
+                "invalid parameter mode {mode} in instruction {instruction} at address {address}"
+            ),
+            IntcodeError::NegativeAddress {
+                address,
+                instruction,
+                target,
+            } => write!(
+                f,
+                "instruction {instruction} at address {address} accessed negative address {target}"
+            ),
+            IntcodeError::AddressOverflow {
+                address,
+                instruction,
+            } => write!(
+                f,
+                "instruction {instruction} at address {address} overflowed while resolving an address"
+            ),
+            IntcodeError::ImmediateWrite {
+                address,
+                instruction,
+                opcode,
+            } => write!(
+                f,
+                "{opcode:?} instruction {instruction} at address {address} writes in immediate mode"
+            ),
+            IntcodeError::MemoryLimitExceeded {
+                address,
+                instruction,
+                target,
+                limit,
+            } => write!(
+                f,
+                "instruction {instruction} at address {address} wrote to {target}, exceeding the memory limit of {limit} cells"
+            ),
+            IntcodeError::InvalidJump {
+                address,
+                instruction,
+                target,
+            } => write!(
+                f,
+                "instruction {instruction} at address {address} jumped to invalid address {target}"
+            ),
+            IntcodeError::TruncatedInstruction {
+                address,
+                instruction,
+                opcode,
+            } => write!(
+                f,
+                "{opcode:?} instruction {instruction} at address {address} runs past the end of the program"
+            ),
+            IntcodeError::InstructionPointerOutOfBounds { address } => {
+                write!(f, "instruction pointer {address} is out of bounds")
+            }
+            IntcodeError::ArithmeticOverflow {
+                address,
+                instruction,
+                opcode,
+            } => write!(
+                f,
+                "{opcode:?} instruction {instruction} at address {address} overflowed a 64-bit value"
+            ),
+            IntcodeError::ExtensionFault {
+                address,
+                instruction,
+                reason,
+            } => write!(f, "instruction {instruction} at address {address} {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for IntcodeError {}
@@ -0,0 +1,306 @@
+use std::{
+    fmt::{self, Display},
+    panic::{self, AssertUnwindSafe},
+};
+
+use enum_iterator::all;
+
+use super::{step, IntcodeError, IntcodeMachine, Jit, Opcode, State, Stepper};
+
+/// Most instructions a single invariant check runs a program for.
+const MAX_STEPS: usize = 10_000;
+
+/// A small xorshift generator, so runs can be reproduced from their seed.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // splitmix64 spreads nearby seeds apart, and xorshift gets stuck on zero
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        Rng((z ^ (z >> 31)).max(1))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+
+    /// A value in `0..n`.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n.max(1) as u64) as usize
+    }
+
+    /// A value in `low..high`.
+    pub fn range(&mut self, low: i64, high: i64) -> i64 {
+        low + self.below((high - low) as usize) as i64
+    }
+}
+
+/// Produces random but well-formed programs: every instruction has a valid opcode and
+/// modes and never writes in immediate mode, though jumps can still land anywhere and
+/// addresses can still be negative.
+#[derive(Debug, Clone)]
+pub struct ProgramGenerator {
+    rng: Rng,
+    /// Number of instructions in each program, before the final `HLT`.
+    pub instructions: usize,
+}
+
+impl ProgramGenerator {
+    pub fn new(seed: u64) -> Self {
+        ProgramGenerator {
+            rng: Rng::new(seed),
+            instructions: 12,
+        }
+    }
+
+    pub fn program(&mut self) -> Vec<i64> {
+        let opcodes = all::<Opcode>().collect::<Vec<_>>();
+        let data = 8;
+        let length = (4 * self.instructions + 1 + data) as i64;
+        let mut program = Vec::new();
+
+        for _ in 0..self.instructions {
+            let opcode = opcodes[self.rng.below(opcodes.len())];
+            let mut instruction = opcode.number();
+            let mut parameters = Vec::new();
+            let mut place = 100;
+            for n in 1..opcode.length() {
+                let writes = opcode.written_parameter() == Some(n);
+                let (mode, parameter) = match self.rng.below(if writes { 2 } else { 3 }) {
+                    0 => (0, self.rng.range(0, length + 8)),
+                    1 => (2, self.rng.range(-4, length)),
+                    _ => match opcode {
+                        Opcode::JumpIfTrue | Opcode::JumpIfFalse if n == 2 => {
+                            (1, self.rng.range(-1, length))
+                        }
+                        Opcode::AdjustRelativeBase => (1, self.rng.range(-10, 10)),
+                        _ => (1, self.rng.range(-20, 100)),
+                    },
+                };
+                instruction += mode * place;
+                place *= 10;
+                parameters.push(parameter);
+            }
+            program.push(instruction);
+            program.extend(parameters);
+        }
+
+        program.push(Opcode::Halt.number());
+        program.extend((0..data).map(|_| self.rng.range(-20, 100)));
+        program
+    }
+
+    pub fn inputs(&mut self) -> Vec<i64> {
+        (0..self.rng.below(4))
+            .map(|_| self.rng.range(-5, 50))
+            .collect()
+    }
+}
+
+/// An invariant of the interpreter a program broke.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    Panic(String),
+    /// A fault changed the machine, rather than leaving it on the faulting instruction.
+    FaultChangedMachine(IntcodeError),
+    /// Halting or blocking on input moved the instruction pointer.
+    MovedWhileStopped(State),
+    /// Running the same machine twice, or a clone of it, gave different results.
+    Nondeterministic,
+    /// Feeding inputs one at a time as the machine blocked differed from queueing them up
+    /// front.
+    ResumptionDiverged,
+    /// The `Jit` backend disagreed with the interpreter.
+    BackendsDiverged,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Panic(message) => write!(f, "panicked: {message}"),
+            Violation::FaultChangedMachine(error) => {
+                write!(f, "fault changed the machine: {error}")
+            }
+            Violation::MovedWhileStopped(state) => {
+                write!(f, "instruction pointer moved on {state:?}")
+            }
+            Violation::Nondeterministic => write!(f, "runs of the same machine differed"),
+            Violation::ResumptionDiverged => {
+                write!(f, "resuming after blocking differed from inputs up front")
+            }
+            Violation::BackendsDiverged => write!(f, "the jit disagreed with the interpreter"),
+        }
+    }
+}
+
+impl std::error::Error for Violation {}
+
+fn load(program: &[i64], inputs: &[i64]) -> IntcodeMachine {
+    let mut machine = IntcodeMachine::from_program(program.to_vec());
+    machine.inputs.extend(inputs);
+    machine
+}
+
+fn same(a: &IntcodeMachine, b: &IntcodeMachine) -> bool {
+    a.program == b.program
+        && a.instruction_pointer == b.instruction_pointer
+        && a.relative_base == b.relative_base
+        && a.inputs == b.inputs
+        && a.outputs == b.outputs
+}
+
+/// Where a bounded run ended: `None` if it ran out of steps.
+type Outcome = Option<Result<State, IntcodeError>>;
+
+/// Steps the machine at most `limit` times with `step`, which may be any backend.
+fn run(
+    machine: &mut IntcodeMachine,
+    limit: usize,
+    mut step: impl FnMut(&mut IntcodeMachine) -> Result<State, IntcodeError>,
+) -> Outcome {
+    for _ in 0..limit {
+        match step(machine) {
+            Ok(State::Active) => (),
+            result => return Some(result),
+        }
+    }
+    None
+}
+
+fn check(program: &[i64], inputs: &[i64]) -> Result<(), Violation> {
+    let mut machine = load(program, inputs);
+    let mut steps = 0;
+    let mut outcome = None;
+    for _ in 0..MAX_STEPS {
+        let before = machine.clone();
+        let result = step(&mut machine);
+        match &result {
+            Ok(State::Active) => {
+                steps += 1;
+                continue;
+            }
+            Ok(state) if machine.instruction_pointer != before.instruction_pointer => {
+                return Err(Violation::MovedWhileStopped(*state))
+            }
+            Err(error) if !same(&machine, &before) => {
+                return Err(Violation::FaultChangedMachine(error.clone()))
+            }
+            _ => (),
+        }
+        outcome = Some(result);
+        break;
+    }
+
+    // a clone taken halfway finishes exactly like the original
+    let mut original = load(program, inputs);
+    run(&mut original, steps / 2, step);
+    let mut clone = original.clone();
+    let first = run(&mut original, MAX_STEPS - steps / 2, step);
+    let second = run(&mut clone, MAX_STEPS - steps / 2, step);
+    if first != outcome
+        || second != outcome
+        || !same(&original, &machine)
+        || !same(&clone, &machine)
+    {
+        return Err(Violation::Nondeterministic);
+    }
+
+    let mut compiled = load(program, inputs);
+    let mut jit = Jit::new(&compiled);
+    if run(&mut compiled, MAX_STEPS, |machine| jit.step(machine)) != outcome
+        || !same(&compiled, &machine)
+    {
+        return Err(Violation::BackendsDiverged);
+    }
+
+    // only a run that finished can be replayed an input at a time within the same budget
+    if outcome.is_some() {
+        let mut resumed = load(program, &[]);
+        let mut pending = inputs.iter();
+        let mut result = run(&mut resumed, MAX_STEPS, step);
+        while result == Some(Ok(State::WaitingForInput)) {
+            let Some(input) = pending.next() else { break };
+            resumed.inputs.push_back(*input);
+            result = run(&mut resumed, MAX_STEPS, step);
+        }
+        resumed.inputs.extend(pending);
+        if result != outcome || !same(&resumed, &machine) {
+            return Err(Violation::ResumptionDiverged);
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs the program through the interpreter and checks it against every invariant,
+/// catching panics rather than propagating them.
+pub fn check_invariants(program: &[i64], inputs: &[i64]) -> Result<(), Violation> {
+    panic::catch_unwind(AssertUnwindSafe(|| check(program, inputs))).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        Err(Violation::Panic(message))
+    })
+}
+
+/// Entry point for coverage-guided fuzzers such as `cargo fuzz`: every two bytes become a
+/// cell of the program, which is then checked without any inputs.
+pub fn fuzz_bytes(data: &[u8]) -> Result<(), Violation> {
+    let program = data
+        .chunks_exact(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]) as i64)
+        .collect::<Vec<_>>();
+    check_invariants(&program, &[])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::format_program;
+
+    #[test]
+    fn test_generated_programs() {
+        for seed in 0..200 {
+            let mut generator = ProgramGenerator::new(seed);
+            let program = generator.program();
+            let inputs = generator.inputs();
+            if let Err(violation) = check_invariants(&program, &inputs) {
+                panic!(
+                    "seed {seed}: {violation}\n{}\ninputs {inputs:?}",
+                    format_program(&program)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_rng_seeds() {
+        for seed in [0, 0x9e37_79b9_7f4a_7c15, u64::MAX] {
+            let mut rng = Rng::new(seed);
+            let values = [rng.next_u64(), rng.next_u64()];
+            assert!(values.iter().all(|value| *value != 0), "seed {seed}");
+            assert_ne!(values[0], values[1], "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn test_fuzz_bytes() {
+        let mut rng = Rng::new(0);
+        for length in 0..200 {
+            let data = (0..length)
+                .map(|_| rng.next_u64() as u8)
+                .collect::<Vec<_>>();
+            assert_eq!(fuzz_bytes(&data), Ok(()));
+        }
+    }
+}
@@ -0,0 +1,353 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, StdinLock, Stdout, Write},
+    path::Path,
+    sync::mpsc::{Receiver, Sender},
+};
+
+use super::{execute_with_budget_and_io, Budget, IntcodeError, IntcodeMachine, State};
+
+/// Where a machine run with [`execute_with_io`] gets its inputs and sends its outputs.
+pub trait IntcodeIo {
+    /// Supplies the next input once the machine asks for one, or `None` to leave it
+    /// waiting for input.
+    fn input(&mut self) -> Option<i64>;
+
+    /// Receives each output as soon as it's produced.
+    fn output(&mut self, value: i64);
+}
+
+/// Runs the machine, handing outputs to `io` as they're produced and pulling inputs from
+/// it whenever the machine's own input queue runs dry. Stops when the machine halts, or
+/// when it needs input and `io` has none.
+pub fn execute_with_io(
+    machine: &mut IntcodeMachine,
+    io: &mut impl IntcodeIo,
+) -> Result<State, IntcodeError> {
+    execute_with_budget_and_io(machine, &Budget::unlimited(), io)
+}
+
+/// Inputs from a queue filled up front, outputs collected into another.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueueIo {
+    pub inputs: VecDeque<i64>,
+    pub outputs: VecDeque<i64>,
+}
+
+impl QueueIo {
+    pub fn new(inputs: impl IntoIterator<Item = i64>) -> Self {
+        QueueIo {
+            inputs: inputs.into_iter().collect(),
+            outputs: VecDeque::new(),
+        }
+    }
+}
+
+impl IntcodeIo for QueueIo {
+    fn input(&mut self) -> Option<i64> {
+        self.inputs.pop_front()
+    }
+
+    fn output(&mut self, value: i64) {
+        self.outputs.push_back(value);
+    }
+}
+
+/// Inputs and outputs handled by a pair of closures, see [`from_fn`].
+pub struct FnIo<I, O> {
+    input: I,
+    output: O,
+}
+
+/// Builds an [`IntcodeIo`] out of a closure producing inputs and one consuming outputs.
+pub fn from_fn<I, O>(input: I, output: O) -> FnIo<I, O>
+where
+    I: FnMut() -> Option<i64>,
+    O: FnMut(i64),
+{
+    FnIo { input, output }
+}
+
+impl<I, O> IntcodeIo for FnIo<I, O>
+where
+    I: FnMut() -> Option<i64>,
+    O: FnMut(i64),
+{
+    fn input(&mut self) -> Option<i64> {
+        (self.input)()
+    }
+
+    fn output(&mut self, value: i64) {
+        (self.output)(value)
+    }
+}
+
+/// Inputs received from and outputs sent to other threads. Asking for input blocks until
+/// a value arrives, or gives up once every sender is gone; outputs sent after the
+/// receiving end hung up are dropped.
+#[derive(Debug)]
+pub struct ChannelIo {
+    pub receiver: Receiver<i64>,
+    pub sender: Sender<i64>,
+}
+
+impl IntcodeIo for ChannelIo {
+    fn input(&mut self) -> Option<i64> {
+        self.receiver.recv().ok()
+    }
+
+    fn output(&mut self, value: i64) {
+        let _ = self.sender.send(value);
+    }
+}
+
+/// Text in, text out: input is read a line at a time and fed to the machine as ASCII
+/// codes, outputs are written as characters. Outputs outside the ASCII range, like the
+/// answers most ASCII programs end with, are written as numbers on their own line.
+///
+/// Once reading or writing fails the machine gets no more input; the failure is kept in
+/// [`AsciiIo::error`].
+#[derive(Debug)]
+pub struct AsciiIo<R, W> {
+    reader: R,
+    writer: W,
+    line: VecDeque<u8>,
+    /// Whether the last output ended a line, or there was none yet.
+    line_start: bool,
+    error: Option<io::Error>,
+}
+
+impl<R: BufRead, W: Write> AsciiIo<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        AsciiIo {
+            reader,
+            writer,
+            line: VecDeque::new(),
+            line_start: true,
+            error: None,
+        }
+    }
+
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    pub fn into_inner(self) -> (R, W) {
+        (self.reader, self.writer)
+    }
+}
+
+impl AsciiIo<StdinLock<'static>, Stdout> {
+    /// Talks to the machine over the terminal.
+    pub fn stdio() -> Self {
+        AsciiIo::new(io::stdin().lock(), io::stdout())
+    }
+}
+
+impl<R: BufRead, W: Write> IntcodeIo for AsciiIo<R, W> {
+    fn input(&mut self) -> Option<i64> {
+        if self.error.is_some() {
+            return None;
+        }
+        if self.line.is_empty() {
+            // make sure any prompt is visible before blocking on the reader
+            let mut line = String::new();
+            match self
+                .writer
+                .flush()
+                .and_then(|_| self.reader.read_line(&mut line))
+            {
+                Ok(_) => self.line.extend(line.bytes()),
+                Err(e) => self.error = Some(e),
+            }
+        }
+        self.line.pop_front().map(i64::from)
+    }
+
+    fn output(&mut self, value: i64) {
+        if self.error.is_some() {
+            return;
+        }
+        let written = match u8::try_from(value) {
+            Ok(byte) if byte.is_ascii() => {
+                self.line_start = byte == b'\n';
+                self.writer.write_all(&[byte])
+            }
+            _ if self.line_start => writeln!(self.writer, "{value}"),
+            _ => {
+                self.line_start = true;
+                writeln!(self.writer, "\n{value}")
+            }
+        };
+        if let Err(e) = written {
+            self.error = Some(e);
+        }
+    }
+}
+
+/// Numbers in, numbers out: inputs are read as integers separated by whitespace or commas,
+/// outputs are written one per line. Errors are handled as in [`AsciiIo`].
+#[derive(Debug)]
+pub struct NumberIo<R, W> {
+    reader: R,
+    writer: W,
+    pending: VecDeque<i64>,
+    error: Option<io::Error>,
+}
+
+impl<R: BufRead, W: Write> NumberIo<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        NumberIo {
+            reader,
+            writer,
+            pending: VecDeque::new(),
+            error: None,
+        }
+    }
+
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    pub fn into_inner(self) -> (R, W) {
+        (self.reader, self.writer)
+    }
+
+    fn read_line(&mut self) -> io::Result<usize> {
+        let mut line = String::new();
+        self.writer.flush()?;
+        let read = self.reader.read_line(&mut line)?;
+        for number in line
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|n| !n.is_empty())
+        {
+            let number = number
+                .parse()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            self.pending.push_back(number);
+        }
+        Ok(read)
+    }
+}
+
+impl NumberIo<StdinLock<'static>, Stdout> {
+    /// Reads inputs from stdin and writes outputs to stdout.
+    pub fn stdio() -> Self {
+        NumberIo::new(io::stdin().lock(), io::stdout())
+    }
+}
+
+impl NumberIo<BufReader<File>, BufWriter<File>> {
+    /// Reads inputs from one file and writes outputs to another.
+    pub fn files(input: &Path, output: &Path) -> io::Result<Self> {
+        Ok(NumberIo::new(
+            BufReader::new(File::open(input)?),
+            BufWriter::new(File::create(output)?),
+        ))
+    }
+}
+
+impl<R: BufRead, W: Write> IntcodeIo for NumberIo<R, W> {
+    fn input(&mut self) -> Option<i64> {
+        while self.pending.is_empty() && self.error.is_none() {
+            match self.read_line() {
+                Ok(0) => return None,
+                Ok(_) => (),
+                Err(e) => self.error = Some(e),
+            }
+        }
+        self.pending.pop_front()
+    }
+
+    fn output(&mut self, value: i64) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(e) = writeln!(self.writer, "{value}") {
+            self.error = Some(e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::parse_machine;
+    use std::{sync::mpsc, thread};
+
+    // adds up pairs of inputs until it sees a 0
+    const ADDER: &str = "3,100,1006,100,16,3,101,1,100,101,101,4,101,1105,1,0,99";
+
+    fn adder() -> IntcodeMachine {
+        let (_, machine) = parse_machine(ADDER).unwrap();
+        machine
+    }
+
+    #[test]
+    fn test_queue() {
+        let mut machine = adder();
+        let mut io = QueueIo::new([1, 2, 3, 4]);
+        assert_eq!(
+            execute_with_io(&mut machine, &mut io),
+            Ok(State::WaitingForInput)
+        );
+        assert_eq!(io.outputs, [3, 7]);
+
+        io.inputs.extend([5, 6, 0]);
+        assert_eq!(
+            execute_with_io(&mut machine, &mut io),
+            Ok(State::Terminated)
+        );
+        assert_eq!(io.outputs, [3, 7, 11]);
+    }
+
+    #[test]
+    fn test_closures() {
+        let mut inputs = [1, 2, 3, 4, 0].into_iter();
+        let mut outputs = Vec::new();
+        let mut io = from_fn(|| inputs.next(), |value| outputs.push(value));
+        assert_eq!(
+            execute_with_io(&mut adder(), &mut io),
+            Ok(State::Terminated)
+        );
+        assert_eq!(outputs, [3, 7]);
+    }
+
+    #[test]
+    fn test_channels() {
+        let (input, receiver) = mpsc::channel();
+        let (sender, output) = mpsc::channel();
+        let runner = thread::spawn(move || {
+            execute_with_io(&mut adder(), &mut ChannelIo { receiver, sender })
+        });
+
+        input.send(20).unwrap();
+        input.send(22).unwrap();
+        assert_eq!(output.recv(), Ok(42));
+        drop(input);
+        assert_eq!(runner.join().unwrap(), Ok(State::WaitingForInput));
+    }
+
+    #[test]
+    fn test_text() {
+        // echoes its input back, line by line
+        let (_, mut machine) = parse_machine("3,7,4,7,1105,1,0,0").unwrap();
+        let mut io = AsciiIo::new("hi\nthere\n".as_bytes(), Vec::new());
+        execute_with_io(&mut machine, &mut io).unwrap();
+        assert_eq!(io.into_inner().1, b"hi\nthere\n");
+
+        let mut io = AsciiIo::new("".as_bytes(), Vec::new());
+        execute_with_io(
+            &mut parse_machine("104,65,104,1000,104,1000,99").unwrap().1,
+            &mut io,
+        )
+        .unwrap();
+        assert_eq!(io.into_inner().1, b"A\n1000\n1000\n");
+
+        let mut io = NumberIo::new("1, 2\n3 4\n0\n".as_bytes(), Vec::new());
+        execute_with_io(&mut adder(), &mut io).unwrap();
+        assert!(io.error().is_none());
+        assert_eq!(io.into_inner().1, b"3\n7\n");
+    }
+}
@@ -0,0 +1,252 @@
+use super::{
+    fetch, operand, resolve, step, Arithmetic, Decoded, IntcodeError, IntcodeMachine, Opcode,
+    State, Stepper,
+};
+
+#[derive(Debug, Clone, Copy)]
+enum Slot {
+    Uncompiled,
+    Compiled(Decoded),
+    /// Not worth compiling, or possibly written to since: always left to the interpreter.
+    Interpreted,
+}
+
+/// A second backend that decodes each instruction of the loaded program once, the first
+/// time it runs, and from then on runs the decoded instruction on the same
+/// [`IntcodeMachine`] state as [`execute`](super::execute). Backends can be swapped
+/// between runs.
+///
+/// Instructions that might have been written to since they were compiled, anything that
+/// would fault or overflow, and everything once a machine under [`Arithmetic::Arbitrary`]
+/// holds a value too big for an `i64`, go through the interpreter in [`step`](super::step)
+/// instead. Only writes made while running through the `Jit` are noticed, so build a new
+/// one after patching a machine's code by hand.
+#[derive(Debug, Clone)]
+pub struct Jit {
+    slots: Vec<Slot>,
+    /// Instructions executed by the interpreter rather than from their decoded form.
+    pub interpreted: u64,
+}
+
+fn value(machine: &IntcodeMachine, op: &Decoded, n: usize) -> Option<i64> {
+    operand(machine, op, n).ok()
+}
+
+/// Applies the machine's arithmetic policy, or returns `None` to leave an overflow to the
+/// interpreter.
+fn arithmetic(
+    machine: &IntcodeMachine,
+    (a, b): (i64, i64),
+    checked: fn(i64, i64) -> Option<i64>,
+    wrapping: fn(i64, i64) -> i64,
+) -> Option<i64> {
+    match machine.arithmetic {
+        Arithmetic::Wrapping => Some(wrapping(a, b)),
+        _ => checked(a, b),
+    }
+}
+
+impl Jit {
+    /// Prepares to compile the machine's program, up to its current length.
+    pub fn new(machine: &IntcodeMachine) -> Self {
+        Jit {
+            slots: vec![Slot::Uncompiled; machine.program.len()],
+            interpreted: 0,
+        }
+    }
+
+    /// Number of instructions compiled so far and still valid.
+    pub fn compiled(&self) -> usize {
+        self.slots
+            .iter()
+            .filter(|slot| matches!(slot, Slot::Compiled(_)))
+            .count()
+    }
+
+    /// Decodes the instruction at the instruction pointer.
+    fn compile(&self, machine: &IntcodeMachine) -> Slot {
+        match fetch(machine) {
+            Ok(op) if op.address + op.opcode.length() <= self.slots.len() => Slot::Compiled(op),
+            _ => Slot::Interpreted,
+        }
+    }
+
+    /// Stops compiling anything a write to `target` could have changed, including code
+    /// that hasn't been compiled yet.
+    fn invalidate(&mut self, target: usize) {
+        if target >= self.slots.len() {
+            return;
+        }
+        for start in target.saturating_sub(3)..=target {
+            let covers = match self.slots[start] {
+                Slot::Compiled(op) => start + op.opcode.length() > target,
+                Slot::Uncompiled => true,
+                Slot::Interpreted => false,
+            };
+            if covers {
+                self.slots[start] = Slot::Interpreted;
+            }
+        }
+    }
+
+    fn interpret(&mut self, machine: &mut IntcodeMachine) -> Result<State, IntcodeError> {
+        self.interpreted += 1;
+        let target = fetch(machine).ok().and_then(|instruction| {
+            let n = instruction.opcode.written_parameter()? - 1;
+            resolve(machine, &instruction, n).ok()
+        });
+
+        let state = step(machine)?;
+        if let (State::Active, Some(target)) = (state, target) {
+            self.invalidate(target);
+        }
+        Ok(state)
+    }
+
+    /// Runs a compiled instruction, or returns `None` without touching the machine if it
+    /// needs the interpreter to raise a fault.
+    fn run(&mut self, machine: &mut IntcodeMachine, op: Decoded) -> Option<State> {
+        let mut next = machine.instruction_pointer + op.opcode.length();
+
+        let store = match op.opcode {
+            Opcode::Addition => {
+                let operands = (value(machine, &op, 0)?, value(machine, &op, 1)?);
+                let sum = arithmetic(machine, operands, i64::checked_add, i64::wrapping_add)?;
+                Some((2, sum))
+            }
+            Opcode::Multiplication => {
+                let operands = (value(machine, &op, 0)?, value(machine, &op, 1)?);
+                let product = arithmetic(machine, operands, i64::checked_mul, i64::wrapping_mul)?;
+                Some((2, product))
+            }
+            Opcode::LessThan => Some((
+                2,
+                (value(machine, &op, 0)? < value(machine, &op, 1)?) as i64,
+            )),
+            Opcode::Equals => Some((
+                2,
+                (value(machine, &op, 0)? == value(machine, &op, 1)?) as i64,
+            )),
+            Opcode::Input => match machine.inputs.front() {
+                Some(input) => Some((0, *input)),
+                None => return Some(State::WaitingForInput),
+            },
+            Opcode::Output => {
+                let output = value(machine, &op, 0)?;
+                machine.outputs.push_back(output);
+                None
+            }
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                let jump = (value(machine, &op, 0)? != 0) == (op.opcode == Opcode::JumpIfTrue);
+                if jump {
+                    next = value(machine, &op, 1)?.try_into().ok()?;
+                }
+                None
+            }
+            Opcode::AdjustRelativeBase => {
+                machine.relative_base =
+                    machine.relative_base.checked_add(value(machine, &op, 0)?)?;
+                None
+            }
+            Opcode::Halt => return Some(State::Terminated),
+        };
+
+        if let Some((n, value)) = store {
+            let target = resolve(machine, &op, n).ok()?;
+            machine.program.write(target, value).ok()?;
+            self.invalidate(target);
+            if op.opcode == Opcode::Input {
+                machine.inputs.pop_front();
+            }
+        }
+
+        machine.instruction_pointer = next;
+        Some(State::Active)
+    }
+}
+
+impl Stepper for Jit {
+    /// Executes a single instruction, from its decoded form where possible.
+    fn step(&mut self, machine: &mut IntcodeMachine) -> Result<State, IntcodeError> {
+        if machine.arithmetic == Arithmetic::Arbitrary && machine.program.has_wide_cells() {
+            // decoded instructions only read the low 64 bits of each cell
+            return self.interpret(machine);
+        }
+        let ip = machine.instruction_pointer;
+        let slot = match self.slots.get(ip) {
+            Some(Slot::Uncompiled) => {
+                let slot = self.compile(machine);
+                self.slots[ip] = slot;
+                slot
+            }
+            Some(slot) => *slot,
+            None => Slot::Interpreted,
+        };
+
+        match slot {
+            Slot::Compiled(op) => match self.run(machine, op) {
+                Some(state) => Ok(state),
+                None => self.interpret(machine),
+            },
+            _ => self.interpret(machine),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{execute, parse_machine};
+
+    /// Runs both backends on the same machine and checks they end up in the same state.
+    fn cross_check(program: &str, inputs: &[i64]) -> IntcodeMachine {
+        let (_, mut interpreted) = parse_machine(program).unwrap();
+        interpreted.inputs.extend(inputs);
+        let mut compiled = interpreted.clone();
+
+        let expected = execute(&mut interpreted);
+        assert_eq!(Jit::new(&compiled).execute(&mut compiled), expected);
+        assert_eq!(compiled.program, interpreted.program);
+        assert_eq!(
+            compiled.instruction_pointer,
+            interpreted.instruction_pointer
+        );
+        assert_eq!(compiled.relative_base, interpreted.relative_base);
+        assert_eq!(compiled.inputs, interpreted.inputs);
+        assert_eq!(compiled.outputs, interpreted.outputs);
+        compiled
+    }
+
+    #[test]
+    fn test_cross_check() {
+        // day 2, 5 and 9 examples
+        cross_check("1,9,10,3,2,3,11,0,99,30,40,50", &[]);
+        let compare = "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,\
+                       1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,\
+                       1105,1,46,98,99";
+        for input in 6..=10 {
+            cross_check(compare, &[input]);
+        }
+        let quine = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+        assert_eq!(cross_check(quine, &[]).outputs.len(), 16);
+        cross_check("104,1125899906842624,99", &[]);
+
+        // faults land on the same instruction
+        cross_check("1101,1,1,5,109,-7,204,0,99", &[]);
+        cross_check("3,3,1105,-1,9,99", &[]);
+    }
+
+    #[test]
+    fn test_self_modifying() {
+        // bumps the operand of its first instruction, `OUT #5`, on each of three passes
+        let program = "104,5,1001,1,1,1,1001,14,-1,14,1005,14,0,99,3";
+        assert_eq!(cross_check(program, &[]).outputs, [5, 6, 7]);
+
+        let (_, mut machine) = parse_machine(program).unwrap();
+        let mut jit = Jit::new(&machine);
+        jit.execute(&mut machine).unwrap();
+        // both later passes of the `OUT`, and the `HLT` as it sits next to the counter
+        assert_eq!(jit.interpreted, 3);
+        assert_eq!(jit.compiled(), 3);
+    }
+}
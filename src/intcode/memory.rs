@@ -0,0 +1,458 @@
+use num::{BigInt, ToPrimitive};
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::{Index, IndexMut},
+    sync::Arc,
+};
+
+/// Number of cells per page of memory.
+pub const PAGE_SIZE: usize = 1024;
+
+/// Default ceiling on allocated cells, 512 MiB worth of `i64`s.
+pub const DEFAULT_MEMORY_LIMIT: usize = 1 << 26;
+
+/// Dense memory always grows at least this far before writes are paged.
+const MIN_DENSE_GROWTH: usize = 4 * PAGE_SIZE;
+
+/// Pages are shared between clones and only copied once one of them writes to it.
+type Page = Arc<[i64; PAGE_SIZE]>;
+
+fn empty_page() -> Page {
+    Arc::new([0; PAGE_SIZE])
+}
+
+/// How memory past the end of the loaded program is allocated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MemoryModel {
+    /// Grow the dense part of memory to cover every write.
+    Dense,
+    /// Keep everything past the loaded program in sparse pages.
+    Paged,
+    /// Grow dense memory for writes close to its end, page writes far past it.
+    #[default]
+    Adaptive,
+}
+
+/// A write needed more cells than the memory limit allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryLimitExceeded {
+    pub address: usize,
+    pub limit: usize,
+}
+
+/// Anything instructions can be decoded from.
+pub trait Cells {
+    /// Returns the cell at `address`, or `None` if it's outside the program.
+    fn cell(&self, address: usize) -> Option<i64>;
+}
+
+impl Cells for [i64] {
+    fn cell(&self, address: usize) -> Option<i64> {
+        self.get(address).copied()
+    }
+}
+
+/// Intcode memory: contiguous pages holding the program and anything written near it, plus
+/// sparse pages for writes far past its end. Unallocated cells read as 0.
+///
+/// Cloning memory takes constant time: clones share the page table until one of them
+/// writes, which copies the table's page pointers, and each clone duplicates a page the
+/// first time it writes to it.
+///
+/// Values too big for an `i64`, written with [`write_big`](Memory::write_big), are kept
+/// to the side while the cell itself holds their low 64 bits.
+#[derive(Debug, Clone)]
+pub struct Memory {
+    pages: Arc<Pages>,
+    len: usize,
+    model: MemoryModel,
+    limit: usize,
+}
+
+/// The page table, shared between clones like the pages themselves.
+#[derive(Debug, Clone, Default)]
+struct Pages {
+    dense: Vec<Page>,
+    sparse: BTreeMap<usize, Page>,
+    wide: HashMap<usize, BigInt>,
+}
+
+static ZERO: i64 = 0;
+
+impl Memory {
+    pub fn new(cells: Vec<i64>) -> Self {
+        let dense = cells
+            .chunks(PAGE_SIZE)
+            .map(|chunk| {
+                let mut page = [0; PAGE_SIZE];
+                page[..chunk.len()].copy_from_slice(chunk);
+                Arc::new(page)
+            })
+            .collect();
+
+        Memory {
+            pages: Arc::new(Pages {
+                dense,
+                ..Pages::default()
+            }),
+            len: cells.len(),
+            model: MemoryModel::default(),
+            limit: DEFAULT_MEMORY_LIMIT,
+        }
+    }
+
+    pub fn with_model(mut self, model: MemoryModel) -> Self {
+        self.model = model;
+        self
+    }
+
+    /// Sets the maximum number of cells the memory may allocate.
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    pub fn model(&self) -> MemoryModel {
+        self.model
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Length of the dense part of memory, which starts out as the loaded program.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0 && self.pages.sparse.is_empty()
+    }
+
+    /// Number of cells currently allocated, counted in whole pages.
+    pub fn allocated(&self) -> usize {
+        (self.pages.dense.len() + self.pages.sparse.len()) * PAGE_SIZE
+    }
+
+    /// Copies out the dense part of memory.
+    pub fn to_vec(&self) -> Vec<i64> {
+        (0..self.len).map(|address| self[address]).collect()
+    }
+
+    /// Number of pages still shared with `other`, i.e. that neither side has written to
+    /// since one was cloned from the other.
+    pub fn shared_pages(&self, other: &Memory) -> usize {
+        let dense = self
+            .pages
+            .dense
+            .iter()
+            .zip(other.pages.dense.iter())
+            .filter(|(a, b)| Arc::ptr_eq(a, b))
+            .count();
+        let sparse = self
+            .pages
+            .sparse
+            .iter()
+            .filter(|(page, a)| {
+                other
+                    .pages
+                    .sparse
+                    .get(page)
+                    .is_some_and(|b| Arc::ptr_eq(a, b))
+            })
+            .count();
+
+        dense + sparse
+    }
+
+    /// Returns the cell at `address`, or `None` if it was never allocated.
+    #[inline]
+    pub fn get(&self, address: usize) -> Option<i64> {
+        if address < self.len {
+            Some(self.pages.dense[address / PAGE_SIZE][address % PAGE_SIZE])
+        } else {
+            self.pages
+                .sparse
+                .get(&(address / PAGE_SIZE))
+                .map(|page| page[address % PAGE_SIZE])
+        }
+    }
+
+    /// The allocated cells from `address` to the end of its page, empty if it was never
+    /// allocated. Lets callers read neighbouring cells with a single lookup.
+    #[inline]
+    pub fn cells_from(&self, address: usize) -> &[i64] {
+        let (page, offset) = (address / PAGE_SIZE, address % PAGE_SIZE);
+        if address < self.len {
+            let end = (self.len - page * PAGE_SIZE).min(PAGE_SIZE);
+            &self.pages.dense[page][offset..end]
+        } else {
+            self.pages
+                .sparse
+                .get(&page)
+                .map_or(&[], |page| &page[offset..])
+        }
+    }
+
+    /// Returns the cell at `address`, reading unallocated memory as 0.
+    #[inline]
+    pub fn read(&self, address: usize) -> i64 {
+        self.get(address).unwrap_or(0)
+    }
+
+    /// Writes `value` to `address`, allocating memory as needed.
+    #[inline]
+    pub fn write(&mut self, address: usize, value: i64) -> Result<(), MemoryLimitExceeded> {
+        if address < self.len {
+            let pages = Arc::make_mut(&mut self.pages);
+            if !pages.wide.is_empty() {
+                pages.wide.remove(&address);
+            }
+            Arc::make_mut(&mut pages.dense[address / PAGE_SIZE])[address % PAGE_SIZE] = value;
+            return Ok(());
+        }
+        *self.cell_mut(address)? = value;
+        Ok(())
+    }
+
+    /// Returns the cell at `address` at full precision.
+    pub fn read_big(&self, address: usize) -> BigInt {
+        match self.pages.wide.get(&address) {
+            Some(value) => value.clone(),
+            None => BigInt::from(self.read(address)),
+        }
+    }
+
+    /// Writes `value` to `address`, keeping it to the side if it doesn't fit in an `i64`.
+    pub fn write_big(&mut self, address: usize, value: BigInt) -> Result<(), MemoryLimitExceeded> {
+        if let Some(value) = value.to_i64() {
+            return self.write(address, value);
+        }
+        let low = (&value & BigInt::from(u64::MAX)).to_u64().unwrap_or(0) as i64;
+        self.write(address, low)?;
+        Arc::make_mut(&mut self.pages).wide.insert(address, value);
+        Ok(())
+    }
+
+    /// Whether any cell holds a value too big for an `i64`.
+    pub fn has_wide_cells(&self) -> bool {
+        !self.pages.wide.is_empty()
+    }
+
+    /// Cells holding values too big for an `i64`, in address order.
+    pub fn wide_cells(&self) -> Vec<(usize, BigInt)> {
+        let mut cells = self
+            .pages
+            .wide
+            .iter()
+            .map(|(address, value)| (*address, value.clone()))
+            .collect::<Vec<_>>();
+        cells.sort_unstable();
+        cells
+    }
+
+    fn cell_mut(&mut self, address: usize) -> Result<&mut i64, MemoryLimitExceeded> {
+        let allocated = self.allocated();
+        let pages = Arc::make_mut(&mut self.pages);
+        if !pages.wide.is_empty() {
+            pages.wide.remove(&address);
+        }
+        let page = address / PAGE_SIZE;
+        if page < pages.dense.len() {
+            // the rest of the last dense page is already allocated
+            self.len = self.len.max(address + 1);
+            return Ok(&mut Arc::make_mut(&mut pages.dense[page])[address % PAGE_SIZE]);
+        }
+
+        let exceeded = MemoryLimitExceeded {
+            address,
+            limit: self.limit,
+        };
+        let grow = match self.model {
+            MemoryModel::Dense => true,
+            MemoryModel::Paged => false,
+            MemoryModel::Adaptive => {
+                pages.sparse.is_empty() && address < (self.len * 2).max(self.len + MIN_DENSE_GROWTH)
+            }
+        };
+
+        if grow {
+            // counted without walking the pages, which may be far past the limit
+            let new_pages =
+                page + 1 - pages.dense.len() - pages.sparse.range(pages.dense.len()..=page).count();
+            let needed = new_pages.saturating_mul(PAGE_SIZE);
+            if allocated.saturating_add(needed) > self.limit {
+                return Err(exceeded);
+            }
+            while pages.dense.len() <= page {
+                let index = pages.dense.len();
+                match pages.sparse.remove(&index) {
+                    Some(sparse) => {
+                        // keep what was already written to the page
+                        pages.dense.push(sparse);
+                        self.len = (index + 1) * PAGE_SIZE;
+                    }
+                    None => pages.dense.push(empty_page()),
+                }
+            }
+            self.len = self.len.max(address + 1);
+            return Ok(&mut Arc::make_mut(&mut pages.dense[page])[address % PAGE_SIZE]);
+        }
+
+        if !pages.sparse.contains_key(&page) && allocated + PAGE_SIZE > self.limit {
+            return Err(exceeded);
+        }
+        let page = pages.sparse.entry(page).or_insert_with(empty_page);
+        Ok(&mut Arc::make_mut(page)[address % PAGE_SIZE])
+    }
+
+    /// Non-zero cells in the sparse part of memory, in address order.
+    pub fn paged_cells(&self) -> Vec<(usize, i64)> {
+        self.pages
+            .sparse
+            .iter()
+            .flat_map(|(page, cells)| {
+                cells
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, value)| **value != 0)
+                    .map(move |(offset, value)| (page * PAGE_SIZE + offset, *value))
+            })
+            .collect()
+    }
+}
+
+impl Cells for Memory {
+    fn cell(&self, address: usize) -> Option<i64> {
+        self.get(address)
+    }
+}
+
+impl From<Vec<i64>> for Memory {
+    fn from(cells: Vec<i64>) -> Self {
+        Memory::new(cells)
+    }
+}
+
+/// Memory compares by contents, regardless of how it's allocated.
+impl PartialEq for Memory {
+    fn eq(&self, other: &Self) -> bool {
+        (0..self.len.max(other.len)).all(|address| self.read(address) == other.read(address))
+            && self
+                .paged_cells()
+                .iter()
+                .chain(other.paged_cells().iter())
+                .all(|(address, _)| self.read(*address) == other.read(*address))
+            && self.pages.wide == other.pages.wide
+    }
+}
+
+impl Eq for Memory {}
+
+impl Index<usize> for Memory {
+    type Output = i64;
+
+    fn index(&self, address: usize) -> &i64 {
+        if address < self.len {
+            &self.pages.dense[address / PAGE_SIZE][address % PAGE_SIZE]
+        } else {
+            self.pages
+                .sparse
+                .get(&(address / PAGE_SIZE))
+                .map(|page| &page[address % PAGE_SIZE])
+                .unwrap_or(&ZERO)
+        }
+    }
+}
+
+/// Panics if the write would exceed the memory limit; use [`Memory::write`] to handle that.
+impl IndexMut<usize> for Memory {
+    fn index_mut(&mut self, address: usize) -> &mut i64 {
+        self.cell_mut(address)
+            .unwrap_or_else(|e| panic!("address {} exceeds the memory limit", e.address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_far_writes_are_paged() {
+        let mut memory = Memory::new(vec![1, 2, 3]);
+        memory.write(10, 4).unwrap();
+        assert_eq!(memory.len(), 11);
+
+        memory.write(1_000_000_000, 5).unwrap();
+        assert_eq!(memory.len(), 11);
+        assert_eq!(memory.allocated(), 2 * PAGE_SIZE);
+        assert_eq!(memory[1_000_000_000], 5);
+        assert_eq!(memory.read(1_000_000_001), 0);
+        assert_eq!(memory.get(2_000_000_000), None);
+        assert_eq!(memory.paged_cells(), vec![(1_000_000_000, 5)]);
+    }
+
+    #[test]
+    fn test_models() {
+        let mut dense = Memory::new(vec![0]).with_model(MemoryModel::Dense);
+        dense.write(100_000, 1).unwrap();
+        assert_eq!(dense.len(), 100_001);
+
+        let mut paged = Memory::new(vec![0]).with_model(MemoryModel::Paged);
+        paged.write(100_000, 1).unwrap();
+        assert_eq!(paged.len(), 1);
+
+        assert_eq!(dense, paged);
+        assert_ne!(dense, Memory::new(vec![0]));
+
+        // growing over a page keeps what was written to it
+        let mut paged = paged.with_model(MemoryModel::Dense);
+        paged.write(100_001, 2).unwrap();
+        assert_eq!(paged[100_000], 1);
+        assert_eq!(paged[100_001], 2);
+        assert_eq!(paged.paged_cells(), vec![]);
+    }
+
+    #[test]
+    fn test_limit() {
+        let mut memory = Memory::new(vec![0; 10]).with_limit(PAGE_SIZE);
+        assert!(memory.write(100, 1).is_ok());
+        assert_eq!(
+            memory.write(1_000_000, 1),
+            Err(MemoryLimitExceeded {
+                address: 1_000_000,
+                limit: PAGE_SIZE
+            })
+        );
+        assert_eq!(memory.allocated(), PAGE_SIZE);
+
+        // growing dense memory that far must fail without allocating anything
+        let mut memory = Memory::new(vec![0; 10])
+            .with_model(MemoryModel::Dense)
+            .with_limit(1 << 20);
+        assert_eq!(
+            memory.write(1 << 50, 1),
+            Err(MemoryLimitExceeded {
+                address: 1 << 50,
+                limit: 1 << 20
+            })
+        );
+        assert_eq!(memory.allocated(), PAGE_SIZE);
+    }
+
+    #[test]
+    fn test_copy_on_write() {
+        let mut memory = Memory::new(vec![0; 3 * PAGE_SIZE]);
+        memory.write(1_000_000, 1).unwrap();
+        let mut fork = memory.clone();
+        assert!(Arc::ptr_eq(&fork.pages, &memory.pages));
+        assert_eq!(fork.shared_pages(&memory), 4);
+
+        fork.write(PAGE_SIZE, 1).unwrap();
+        assert!(!Arc::ptr_eq(&fork.pages, &memory.pages));
+        assert_eq!(fork.shared_pages(&memory), 3);
+        assert_eq!(memory[PAGE_SIZE], 0);
+        assert_eq!(fork[PAGE_SIZE], 1);
+    }
+}
@@ -0,0 +1,999 @@
+use enum_iterator::Sequence;
+use itertools::Itertools;
+use nom::{bytes::complete::tag, character::complete::i64, multi::separated_list1, IResult};
+
+use std::collections::VecDeque;
+
+pub use arithmetic::*;
+pub use ascii::*;
+pub use assembler::*;
+pub use budget::*;
+pub use cfg::*;
+pub use debugger::*;
+pub use dialect::*;
+pub use disassembler::*;
+pub use error::IntcodeError;
+pub use fuzz::*;
+pub use io::*;
+pub use jit::*;
+pub use memory::*;
+pub use network::*;
+pub use patch::*;
+pub use persist::*;
+pub use profiler::*;
+pub use snapshot::*;
+pub use threads::*;
+pub use topology::*;
+pub use trace::*;
+pub use tracker::*;
+
+mod arithmetic;
+mod ascii;
+mod assembler;
+mod budget;
+mod cfg;
+mod debugger;
+mod dialect;
+mod disassembler;
+mod error;
+mod fuzz;
+mod io;
+mod jit;
+mod memory;
+mod network;
+mod patch;
+mod persist;
+mod profiler;
+mod snapshot;
+mod threads;
+mod topology;
+mod trace;
+mod tracker;
+
+#[derive(Debug, Clone)]
+pub struct IntcodeMachine {
+    pub program: Memory,
+    pub instruction_pointer: usize,
+    pub relative_base: i64,
+    pub inputs: VecDeque<i64>,
+    pub outputs: VecDeque<i64>,
+    pub arithmetic: Arithmetic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+const fn mode_from_digit(n: i64) -> Option<Mode> {
+    match n {
+        0 => Some(Mode::Position),
+        1 => Some(Mode::Immediate),
+        2 => Some(Mode::Relative),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Sequence)]
+pub enum Opcode {
+    Addition,
+    Multiplication,
+    Input,
+    Output,
+    JumpIfTrue,
+    JumpIfFalse,
+    LessThan,
+    Equals,
+    AdjustRelativeBase,
+    Halt,
+}
+
+const fn opcode_from_number(n: &i64) -> Option<Opcode> {
+    match n {
+        1 => Some(Opcode::Addition),
+        2 => Some(Opcode::Multiplication),
+        3 => Some(Opcode::Input),
+        4 => Some(Opcode::Output),
+        5 => Some(Opcode::JumpIfTrue),
+        6 => Some(Opcode::JumpIfFalse),
+        7 => Some(Opcode::LessThan),
+        8 => Some(Opcode::Equals),
+        9 => Some(Opcode::AdjustRelativeBase),
+        99 => Some(Opcode::Halt),
+        _ => None,
+    }
+}
+
+impl Opcode {
+    pub fn number(&self) -> i64 {
+        match self {
+            Opcode::Addition => 1,
+            Opcode::Multiplication => 2,
+            Opcode::Input => 3,
+            Opcode::Output => 4,
+            Opcode::JumpIfTrue => 5,
+            Opcode::JumpIfFalse => 6,
+            Opcode::LessThan => 7,
+            Opcode::Equals => 8,
+            Opcode::AdjustRelativeBase => 9,
+            Opcode::Halt => 99,
+        }
+    }
+
+    /// Number of cells the instruction occupies, including the opcode itself.
+    pub fn length(&self) -> usize {
+        match self {
+            Opcode::Addition | Opcode::Multiplication | Opcode::LessThan | Opcode::Equals => 4,
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => 3,
+            Opcode::Input | Opcode::Output | Opcode::AdjustRelativeBase => 2,
+            Opcode::Halt => 1,
+        }
+    }
+
+    /// The parameter (1-indexed) the instruction writes to, if any.
+    pub fn written_parameter(&self) -> Option<usize> {
+        match self {
+            Opcode::Addition | Opcode::Multiplication | Opcode::LessThan | Opcode::Equals => {
+                Some(3)
+            }
+            Opcode::Input => Some(1),
+            _ => None,
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Opcode::Addition => "ADD",
+            Opcode::Multiplication => "MUL",
+            Opcode::Input => "IN",
+            Opcode::Output => "OUT",
+            Opcode::JumpIfTrue => "JT",
+            Opcode::JumpIfFalse => "JF",
+            Opcode::LessThan => "LT",
+            Opcode::Equals => "EQ",
+            Opcode::AdjustRelativeBase => "ARB",
+            Opcode::Halt => "HLT",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Active,
+    WaitingForInput,
+    Terminated,
+    /// Stopped by [`execute_with_budget`] before the next instruction; the machine can resume.
+    BudgetExhausted(Limit),
+    /// Halted by an extension opcode with an exit code, see [`InstructionSet`].
+    Exited(i64),
+    /// Handed control to the host by an extension opcode, past which the machine resumes.
+    Trapped(i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Instruction {
+    opcode: Opcode,
+    modes: [Mode; 3],
+}
+
+/// One past the largest instruction whose digits are all spelled out: mode 2 for every
+/// parameter and opcode 99.
+const DECODED_LEN: usize = 22300;
+
+/// Every instruction below [`DECODED_LEN`], decoded ahead of time so the interpreter only
+/// has to look it up.
+static DECODED: [Option<Instruction>; DECODED_LEN] = decode_all();
+
+const fn decode_all() -> [Option<Instruction>; DECODED_LEN] {
+    let mut decoded = [None; DECODED_LEN];
+    let mut raw = 0;
+    while raw < DECODED_LEN {
+        let n = raw as i64;
+        if let (Some(opcode), Some(first), Some(second), Some(third)) = (
+            opcode_from_number(&(n % 100)),
+            mode_from_digit(n / 100 % 10),
+            mode_from_digit(n / 1000 % 10),
+            mode_from_digit(n / 10000 % 10),
+        ) {
+            decoded[raw] = Some(Instruction {
+                opcode,
+                modes: [first, second, third],
+            });
+        }
+        raw += 1;
+    }
+    decoded
+}
+
+#[inline]
+fn decode_instruction(address: usize, instruction: &i64) -> Result<Instruction, IntcodeError> {
+    if let Some(Some(decoded)) = usize::try_from(*instruction)
+        .ok()
+        .and_then(|raw| DECODED.get(raw))
+    {
+        return Ok(*decoded);
+    }
+    decode_digits(address, instruction)
+}
+
+fn decode_digits(address: usize, instruction: &i64) -> Result<Instruction, IntcodeError> {
+    let opcode = opcode_from_number(&(instruction % 100)).ok_or(IntcodeError::UnknownOpcode {
+        address,
+        instruction: *instruction,
+        opcode: instruction % 100,
+    })?;
+    let mode = |place: i64| {
+        let digit = instruction / place % 10;
+        mode_from_digit(digit).ok_or(IntcodeError::InvalidMode {
+            address,
+            instruction: *instruction,
+            mode: digit,
+        })
+    };
+
+    Ok(Instruction {
+        opcode,
+        modes: [mode(100)?, mode(1000)?, mode(10000)?],
+    })
+}
+
+impl IntcodeMachine {
+    /// A machine about to run `program` from its first cell, with nothing queued.
+    pub fn from_program(program: Vec<i64>) -> Self {
+        IntcodeMachine {
+            program: Memory::from(program),
+            instruction_pointer: 0,
+            relative_base: 0,
+            inputs: VecDeque::new(),
+            outputs: VecDeque::new(),
+            arithmetic: Arithmetic::default(),
+        }
+    }
+
+    /// Caps the number of cells the machine's memory may allocate, see
+    /// [`Memory::with_limit`].
+    pub fn with_memory_limit(self, limit: usize) -> Self {
+        IntcodeMachine {
+            program: self.program.with_limit(limit),
+            ..self
+        }
+    }
+}
+
+pub fn parse_machine(i: &str) -> IResult<&str, IntcodeMachine> {
+    let (i, program) = separated_list1(tag(","), i64)(i)?;
+
+    Ok((i, IntcodeMachine::from_program(program)))
+}
+
+/// Formats a program the way `parse_machine` reads it.
+pub fn format_program(program: &[i64]) -> String {
+    program.iter().join(",")
+}
+
+/// An instruction read from memory along with its parameters, which are copied out up
+/// front since the instruction may overwrite itself.
+#[derive(Debug, Clone, Copy)]
+struct Decoded {
+    address: usize,
+    raw: i64,
+    opcode: Opcode,
+    modes: [Mode; 3],
+    parameters: [i64; 3],
+}
+
+/// Reads and decodes the instruction at the instruction pointer.
+#[inline]
+fn fetch(machine: &IntcodeMachine) -> Result<Decoded, IntcodeError> {
+    let address = machine.instruction_pointer;
+    let raw = machine
+        .program
+        .get(address)
+        .ok_or(IntcodeError::InstructionPointerOutOfBounds { address })?;
+    let instruction = decode_instruction(address, &raw)?;
+
+    let mut parameters = [0; 3];
+    for (n, parameter) in parameters
+        .iter_mut()
+        .enumerate()
+        .take(instruction.opcode.length() - 1)
+    {
+        *parameter =
+            machine
+                .program
+                .get(address + 1 + n)
+                .ok_or(IntcodeError::TruncatedInstruction {
+                    address,
+                    instruction: raw,
+                    opcode: instruction.opcode,
+                })?;
+    }
+
+    Ok(Decoded {
+        address,
+        raw,
+        opcode: instruction.opcode,
+        modes: instruction.modes,
+        parameters,
+    })
+}
+
+/// Where a parameter points: itself in position mode, offset by the relative base in
+/// relative mode, and nowhere in immediate mode. Faults are blamed on the instruction at
+/// `address`.
+#[inline]
+fn locate(
+    machine: &IntcodeMachine,
+    address: usize,
+    raw: i64,
+    mode: Mode,
+    parameter: i64,
+) -> Result<Option<usize>, IntcodeError> {
+    let offset = match mode {
+        Mode::Position => 0,
+        Mode::Relative => machine.relative_base,
+        Mode::Immediate => return Ok(None),
+    };
+    let target = parameter
+        .checked_add(offset)
+        .ok_or(IntcodeError::AddressOverflow {
+            address,
+            instruction: raw,
+        })?;
+    let target = target
+        .try_into()
+        .map_err(|_| IntcodeError::NegativeAddress {
+            address,
+            instruction: raw,
+            target,
+        })?;
+    Ok(Some(target))
+}
+
+/// The address the `n`th parameter (0-indexed) of the instruction points to, faulting if
+/// it's in immediate mode.
+#[inline]
+fn resolve(
+    machine: &IntcodeMachine,
+    instruction: &Decoded,
+    n: usize,
+) -> Result<usize, IntcodeError> {
+    let Decoded {
+        address,
+        raw,
+        opcode,
+        ..
+    } = *instruction;
+    locate(
+        machine,
+        address,
+        raw,
+        instruction.modes[n],
+        instruction.parameters[n],
+    )?
+    .ok_or(IntcodeError::ImmediateWrite {
+        address,
+        instruction: raw,
+        opcode,
+    })
+}
+
+/// The value of the `n`th parameter (0-indexed) of the instruction.
+#[inline]
+fn operand(machine: &IntcodeMachine, instruction: &Decoded, n: usize) -> Result<i64, IntcodeError> {
+    match instruction.modes[n] {
+        Mode::Immediate => Ok(instruction.parameters[n]),
+        Mode::Position | Mode::Relative => {
+            Ok(machine.program.read(resolve(machine, instruction, n)?))
+        }
+    }
+}
+
+/// Executes a single instruction. On a fault the machine is left untouched, pointing at
+/// the faulting instruction.
+pub fn step(machine: &mut IntcodeMachine) -> Result<State, IntcodeError> {
+    if machine.arithmetic == Arithmetic::Arbitrary && machine.program.has_wide_cells() {
+        return step_wide(machine);
+    }
+    let instruction = fetch(machine)?;
+    let Decoded {
+        address,
+        raw,
+        opcode,
+        ..
+    } = instruction;
+    let mut next = address + opcode.length();
+
+    macro_rules! value {
+        ($n:expr) => {
+            operand(machine, &instruction, $n)?
+        };
+    }
+
+    macro_rules! store {
+        ($n:expr, $value:expr) => {{
+            let value = $value;
+            let target = resolve(machine, &instruction, $n)?;
+            machine.program.write(target, value).map_err(|e| {
+                IntcodeError::MemoryLimitExceeded {
+                    address,
+                    instruction: raw,
+                    target,
+                    limit: e.limit,
+                }
+            })?;
+        }};
+    }
+
+    macro_rules! arithmetic {
+        ($checked:ident, $wrapping:ident) => {{
+            let (a, b) = (value!(0), value!(1));
+            match (machine.arithmetic, a.$checked(b)) {
+                (Arithmetic::Wrapping, _) => a.$wrapping(b),
+                (_, Some(value)) => value,
+                (Arithmetic::Arbitrary, None) => return step_wide(machine),
+                (Arithmetic::Checked, None) => {
+                    return Err(IntcodeError::ArithmeticOverflow {
+                        address,
+                        instruction: raw,
+                        opcode,
+                    })
+                }
+            }
+        }};
+    }
+
+    macro_rules! jump {
+        ($target:expr) => {{
+            let target = $target;
+            next = target.try_into().map_err(|_| IntcodeError::InvalidJump {
+                address,
+                instruction: raw,
+                target,
+            })?;
+        }};
+    }
+
+    match opcode {
+        Opcode::Addition => store!(2, arithmetic!(checked_add, wrapping_add)),
+        Opcode::Multiplication => store!(2, arithmetic!(checked_mul, wrapping_mul)),
+        Opcode::Input => {
+            let Some(input) = machine.inputs.front().copied() else {
+                // stay put and wait to try again
+                return Ok(State::WaitingForInput);
+            };
+            store!(0, input);
+            machine.inputs.pop_front();
+        }
+        Opcode::Output => {
+            let output = value!(0);
+            machine.outputs.push_back(output);
+        }
+        Opcode::JumpIfTrue => {
+            if value!(0) != 0 {
+                jump!(value!(1));
+            }
+        }
+        Opcode::JumpIfFalse => {
+            if value!(0) == 0 {
+                jump!(value!(1));
+            }
+        }
+        Opcode::LessThan => store!(2, (value!(0) < value!(1)) as i64),
+        Opcode::Equals => store!(2, (value!(0) == value!(1)) as i64),
+        Opcode::AdjustRelativeBase => {
+            machine.relative_base = machine.relative_base.checked_add(value!(0)).ok_or(
+                IntcodeError::AddressOverflow {
+                    address,
+                    instruction: raw,
+                },
+            )?;
+        }
+        Opcode::Halt => {
+            return Ok(State::Terminated);
+        }
+    }
+
+    machine.instruction_pointer = next;
+    // still active
+    Ok(State::Active)
+}
+
+/// Executes a machine one instruction at a time: the interpreter itself, or a backend or
+/// instrumented wrapper around it. Backends can be swapped between runs.
+pub trait Stepper {
+    /// Executes a single instruction.
+    fn step(&mut self, machine: &mut IntcodeMachine) -> Result<State, IntcodeError>;
+
+    /// Runs the machine until it halts, blocks on input or otherwise stops, returning the
+    /// first fault.
+    fn execute(&mut self, machine: &mut IntcodeMachine) -> Result<State, IntcodeError> {
+        loop {
+            let active = self.step(machine)?;
+            match active {
+                State::Active => (),
+                _ => break Ok(active),
+            }
+        }
+    }
+}
+
+/// The plain interpreter, [`step`], as a [`Stepper`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Interpreter;
+
+impl Stepper for Interpreter {
+    #[inline]
+    fn step(&mut self, machine: &mut IntcodeMachine) -> Result<State, IntcodeError> {
+        step(machine)
+    }
+}
+
+/// Runs the machine until it halts or blocks on input, returning the first fault instead
+/// of panicking. A faulted machine is left at the faulting instruction.
+pub fn execute(machine: &mut IntcodeMachine) -> Result<State, IntcodeError> {
+    Interpreter.execute(machine)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn machine(program: &str) -> IntcodeMachine {
+        parse_machine(program).unwrap().1
+    }
+
+    #[test]
+    fn test_decoded_table() {
+        for raw in -100..DECODED_LEN as i64 + 1000 {
+            assert_eq!(decode_instruction(0, &raw), decode_digits(0, &raw), "{raw}");
+        }
+    }
+
+    #[test]
+    fn test_unknown_opcode() {
+        let mut machine = machine("1,0,0,0,42");
+        let result = execute(&mut machine);
+        assert_eq!(
+            result,
+            Err(IntcodeError::UnknownOpcode {
+                address: 4,
+                instruction: 42,
+                opcode: 42
+            })
+        );
+        assert_eq!(machine.instruction_pointer, 4);
+    }
+
+    #[test]
+    fn test_invalid_mode() {
+        let mut machine = machine("301,0,0,0,99");
+        let result = execute(&mut machine);
+        assert!(matches!(
+            result,
+            Err(IntcodeError::InvalidMode { mode: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn test_negative_address() {
+        let mut machine = machine("1,-1,0,0,99");
+        let result = execute(&mut machine);
+        assert_eq!(
+            result,
+            Err(IntcodeError::NegativeAddress {
+                address: 0,
+                instruction: 1,
+                target: -1
+            })
+        );
+        assert_eq!(machine.instruction_pointer, 0);
+    }
+
+    #[test]
+    fn test_immediate_write() {
+        let mut machine = machine("11101,1,1,0,99");
+        let result = execute(&mut machine);
+        assert_eq!(
+            result,
+            Err(IntcodeError::ImmediateWrite {
+                address: 0,
+                instruction: 11101,
+                opcode: Opcode::Addition
+            })
+        );
+    }
+
+    #[test]
+    fn test_out_of_bounds() {
+        let mut machine = machine("1106,0,10");
+        let result = execute(&mut machine);
+        assert_eq!(
+            result,
+            Err(IntcodeError::InstructionPointerOutOfBounds { address: 10 })
+        );
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        let mut machine =
+            machine("1101,1,1,1000000000,1101,1,1,2000000000,99").with_memory_limit(2 * PAGE_SIZE);
+        let result = execute(&mut machine);
+        assert_eq!(machine.program[1_000_000_000], 2);
+        assert_eq!(
+            result,
+            Err(IntcodeError::MemoryLimitExceeded {
+                address: 4,
+                instruction: 1101,
+                target: 2_000_000_000,
+                limit: 2 * PAGE_SIZE
+            })
+        );
+    }
+
+    #[test]
+    fn test_input_kept_on_fault() {
+        let mut machine = machine("3,-1,99");
+        machine.inputs.push_back(7);
+        assert!(execute(&mut machine).is_err());
+        assert_eq!(machine.inputs, VecDeque::from([7]));
+    }
+
+    // Table-driven conformance suite, run against the interpreter and every backend that
+    // claims to behave like it.
+
+    type Backend = fn(&mut IntcodeMachine) -> Result<State, IntcodeError>;
+
+    fn backends() -> Vec<(&'static str, Backend)> {
+        vec![
+            ("execute", execute),
+            ("jit", |machine| Jit::new(machine).execute(machine)),
+            ("instruction set", |machine| {
+                InstructionSet::full().execute(machine)
+            }),
+            ("budget", |machine| {
+                execute_with_budget(machine, &Budget::unlimited())
+            }),
+            ("tracer", |machine| Tracer::new().execute(machine)),
+            ("profiler", |machine| Profiler::new().execute(machine)),
+            ("tracker", |machine| {
+                MemoryTracker::new(machine).execute(machine)
+            }),
+        ]
+    }
+
+    struct Case {
+        name: &'static str,
+        program: &'static str,
+        inputs: Vec<i64>,
+        outputs: Vec<i64>,
+        result: Result<State, IntcodeError>,
+        /// Cells to check once the machine stops.
+        memory: Vec<(usize, i64)>,
+        /// Where the instruction pointer should be left, if it matters.
+        ip: Option<usize>,
+    }
+
+    fn case(name: &'static str, program: &'static str, inputs: &[i64], outputs: &[i64]) -> Case {
+        Case {
+            name,
+            program,
+            inputs: inputs.to_vec(),
+            outputs: outputs.to_vec(),
+            result: Ok(State::Terminated),
+            memory: Vec::new(),
+            ip: None,
+        }
+    }
+
+    impl Case {
+        fn memory(mut self, cells: &[(usize, i64)]) -> Self {
+            self.memory = cells.to_vec();
+            self
+        }
+
+        fn result(mut self, result: Result<State, IntcodeError>) -> Self {
+            self.result = result;
+            self
+        }
+
+        fn ip(mut self, ip: usize) -> Self {
+            self.ip = Some(ip);
+            self
+        }
+    }
+
+    const DAY_5_COMPARE: &str = "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,\
+                                 98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,\
+                                 1,20,4,20,1105,1,46,98,99";
+    const DAY_9_QUINE: &str = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+
+    fn opcode_cases() -> Vec<Case> {
+        vec![
+            case("add position", "1,5,6,7,99,3,4,0", &[], &[]).memory(&[(7, 7)]),
+            case("add immediate", "1101,3,4,5,99,0", &[], &[]).memory(&[(5, 7)]),
+            case(
+                "add relative",
+                "109,10,22201,0,1,2,99,0,0,0,3,4,0",
+                &[],
+                &[],
+            )
+            .memory(&[(12, 7)]),
+            case("multiply mixed", "1002,4,3,4,33", &[], &[]).memory(&[(4, 99)]),
+            case("multiply relative", "109,7,21202,0,-2,1,99,6,0", &[], &[]).memory(&[(8, -12)]),
+            case("input position", "3,3,99,0", &[5], &[]).memory(&[(3, 5)]),
+            case("input relative", "109,4,203,1,99,0", &[5], &[]).memory(&[(5, 5)]),
+            case("output position", "4,3,99,42", &[], &[42]),
+            case("output immediate", "104,-7,99", &[], &[-7]),
+            case("output relative", "109,3,204,1,99", &[], &[99]),
+            case("jump if true taken", "1105,1,4,99,104,1,99", &[], &[1]),
+            case("jump if true not taken", "1105,0,4,99,104,1,99", &[], &[]).ip(3),
+            case("jump if true position", "5,7,8,99,104,1,99,1,4", &[], &[1]),
+            case(
+                "jump if true relative",
+                "109,6,2205,1,2,99,4,1,9,104,1,99",
+                &[],
+                &[1],
+            ),
+            case("jump if false taken", "1106,0,4,99,104,2,99", &[], &[2]),
+            case("jump if false not taken", "1106,5,4,99,104,2,99", &[], &[]).ip(3),
+            case("jump if false position", "6,7,8,99,104,2,99,0,4", &[], &[2]),
+            case(
+                "jump if false relative",
+                "109,6,2206,1,2,99,4,0,9,104,2,99",
+                &[],
+                &[2],
+            ),
+            case("less than", "1107,1,2,5,99,9", &[], &[]).memory(&[(5, 1)]),
+            case("not less than", "1107,2,1,5,99,9", &[], &[]).memory(&[(5, 0)]),
+            case("less than position", "7,5,6,7,99,2,3,9", &[], &[]).memory(&[(7, 1)]),
+            case("less than relative", "109,6,22207,1,2,3,99,2,3,9", &[], &[]).memory(&[(9, 1)]),
+            case("equals immediate", "1108,3,3,5,99,9", &[], &[]).memory(&[(5, 1)]),
+            case("equals position", "8,5,6,7,99,4,4,9", &[], &[]).memory(&[(7, 1)]),
+            case("equals relative", "109,6,22208,1,2,3,99,4,4,0", &[], &[]).memory(&[(9, 1)]),
+            case("adjust relative base", "109,5,109,-2,204,0,99", &[], &[-2]),
+            case(
+                "adjust relative base position",
+                "9,5,204,-1,99,5",
+                &[],
+                &[99],
+            ),
+            case(
+                "adjust relative base relative",
+                "109,3,209,4,204,-10,99,7",
+                &[],
+                &[109],
+            ),
+            case("halt", "99", &[], &[]).ip(0),
+            case("add overflow", "1101,9223372036854775807,1,0,99", &[], &[])
+                .result(Err(IntcodeError::ArithmeticOverflow {
+                    address: 0,
+                    instruction: 1101,
+                    opcode: Opcode::Addition,
+                }))
+                .memory(&[(0, 1101)])
+                .ip(0),
+            case(
+                "multiply overflow",
+                "1102,-9223372036854775807,2,0,99",
+                &[],
+                &[],
+            )
+            .result(Err(IntcodeError::ArithmeticOverflow {
+                address: 0,
+                instruction: 1102,
+                opcode: Opcode::Multiplication,
+            }))
+            .ip(0),
+        ]
+    }
+
+    fn immediate_write_cases() -> Vec<Case> {
+        let fault = |name, program, inputs: &[i64], instruction, opcode| {
+            case(name, program, inputs, &[])
+                .result(Err(IntcodeError::ImmediateWrite {
+                    address: 0,
+                    instruction,
+                    opcode,
+                }))
+                .ip(0)
+        };
+        vec![
+            fault("add", "11101,1,2,3,99", &[], 11101, Opcode::Addition).memory(&[(3, 3)]),
+            fault(
+                "multiply",
+                "11102,2,3,3,99",
+                &[],
+                11102,
+                Opcode::Multiplication,
+            )
+            .memory(&[(3, 3)]),
+            fault("input", "103,1,99", &[5], 103, Opcode::Input).memory(&[(1, 1)]),
+            fault("less than", "11107,1,2,3,99", &[], 11107, Opcode::LessThan).memory(&[(3, 3)]),
+            fault("equals", "11108,1,1,3,99", &[], 11108, Opcode::Equals).memory(&[(3, 3)]),
+        ]
+    }
+
+    fn relative_base_cases() -> Vec<Case> {
+        vec![
+            case("negative base", "109,-5,21101,1,2,10,204,10,99", &[], &[3]),
+            case("accumulated base", "109,1,109,1,204,-2,99", &[], &[109]),
+            case(
+                "far past the program",
+                "109,1000,21101,1,2,0,204,0,99",
+                &[],
+                &[3],
+            )
+            .memory(&[(1000, 3)]),
+            case("negative address", "109,-1,204,0,99", &[], &[])
+                .result(Err(IntcodeError::NegativeAddress {
+                    address: 2,
+                    instruction: 204,
+                    target: -1,
+                }))
+                .ip(2),
+            case(
+                "base overflow",
+                "109,9223372036854775807,109,1,99",
+                &[],
+                &[],
+            )
+            .result(Err(IntcodeError::AddressOverflow {
+                address: 2,
+                instruction: 109,
+            }))
+            .ip(2),
+        ]
+    }
+
+    fn day_5_cases() -> Vec<Case> {
+        const EQUAL_POSITION: &str = "3,9,8,9,10,9,4,9,99,-1,8";
+        const LESS_POSITION: &str = "3,9,7,9,10,9,4,9,99,-1,8";
+        const EQUAL_IMMEDIATE: &str = "3,3,1108,-1,8,3,4,3,99";
+        const LESS_IMMEDIATE: &str = "3,3,1107,-1,8,3,4,3,99";
+        const JUMP_POSITION: &str = "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9";
+        const JUMP_IMMEDIATE: &str = "3,3,1105,-1,9,1101,0,0,12,4,12,99,1";
+
+        vec![
+            case("equal to 8, position", EQUAL_POSITION, &[8], &[1]),
+            case("not equal to 8, position", EQUAL_POSITION, &[7], &[0]),
+            case("less than 8, position", LESS_POSITION, &[7], &[1]),
+            case("not less than 8, position", LESS_POSITION, &[8], &[0]),
+            case("equal to 8, immediate", EQUAL_IMMEDIATE, &[8], &[1]),
+            case("not equal to 8, immediate", EQUAL_IMMEDIATE, &[9], &[0]),
+            case("less than 8, immediate", LESS_IMMEDIATE, &[5], &[1]),
+            case("not less than 8, immediate", LESS_IMMEDIATE, &[10], &[0]),
+            case("jump on zero, position", JUMP_POSITION, &[0], &[0]),
+            case("jump on nonzero, position", JUMP_POSITION, &[5], &[1]),
+            case("jump on zero, immediate", JUMP_IMMEDIATE, &[0], &[0]),
+            case("jump on nonzero, immediate", JUMP_IMMEDIATE, &[3], &[1]),
+            case("below 8", DAY_5_COMPARE, &[7], &[999]),
+            case("exactly 8", DAY_5_COMPARE, &[8], &[1000]),
+            case("above 8", DAY_5_COMPARE, &[9], &[1001]),
+        ]
+    }
+
+    fn day_9_cases() -> Vec<Case> {
+        let quine = DAY_9_QUINE.split(',').map(|n| n.parse().unwrap());
+        vec![
+            case("quine", DAY_9_QUINE, &[], &quine.collect::<Vec<_>>()),
+            case(
+                "16-digit product",
+                "1102,34915192,34915192,7,4,7,99,0",
+                &[],
+                &[1219070632396864],
+            ),
+            case(
+                "big number",
+                "104,1125899906842624,99",
+                &[],
+                &[1125899906842624],
+            ),
+        ]
+    }
+
+    fn blocking_cases() -> Vec<Case> {
+        vec![
+            case("blocks without input", "3,5,4,5,99,0", &[], &[])
+                .result(Ok(State::WaitingForInput))
+                .ip(0),
+            case("outputs before blocking", "104,1,3,0,99", &[], &[1])
+                .result(Ok(State::WaitingForInput))
+                .ip(2),
+            case(
+                "inputs in order",
+                "3,9,3,10,4,10,4,9,99,0,0",
+                &[1, 2],
+                &[2, 1],
+            ),
+            case(
+                "blocks on the second input",
+                "3,9,3,10,4,10,4,9,99,0,0",
+                &[1],
+                &[],
+            )
+            .result(Ok(State::WaitingForInput))
+            .memory(&[(9, 1)])
+            .ip(2),
+            case("leftover inputs", "3,3,99,0", &[4, 5], &[]).memory(&[(3, 4)]),
+        ]
+    }
+
+    fn check(cases: Vec<Case>) {
+        for case in cases.iter() {
+            for (backend, run) in backends() {
+                let (_, mut machine) = parse_machine(case.program).unwrap();
+                machine.inputs.extend(case.inputs.iter());
+                let result = run(&mut machine);
+
+                let context = format!("{} on {backend}", case.name);
+                assert_eq!(result, case.result, "{context}");
+                assert_eq!(machine.outputs, case.outputs, "{context}");
+                for (address, value) in case.memory.iter() {
+                    assert_eq!(machine.program[*address], *value, "{context}: [{address}]");
+                }
+                if let Some(ip) = case.ip {
+                    assert_eq!(machine.instruction_pointer, ip, "{context}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_opcodes() {
+        check(opcode_cases());
+    }
+
+    #[test]
+    fn test_immediate_writes() {
+        check(immediate_write_cases());
+    }
+
+    #[test]
+    fn test_relative_base() {
+        check(relative_base_cases());
+    }
+
+    #[test]
+    fn test_day_5() {
+        check(day_5_cases());
+    }
+
+    #[test]
+    fn test_day_9() {
+        check(day_9_cases());
+    }
+
+    #[test]
+    fn test_blocking() {
+        check(blocking_cases());
+    }
+
+    #[test]
+    fn test_resumption() {
+        // feeding inputs one at a time after each block ends the same as supplying them up front
+        for case in day_5_cases().into_iter().chain(blocking_cases()) {
+            for (backend, run) in backends() {
+                let (_, mut upfront) = parse_machine(case.program).unwrap();
+                upfront.inputs.extend(case.inputs.iter());
+                let expected = run(&mut upfront);
+
+                let (_, mut resumed) = parse_machine(case.program).unwrap();
+                let mut inputs = case.inputs.iter();
+                let mut result = run(&mut resumed);
+                while result == Ok(State::WaitingForInput) {
+                    let Some(input) = inputs.next() else { break };
+                    resumed.inputs.push_back(*input);
+                    result = run(&mut resumed);
+                }
+
+                let context = format!("{} on {backend}", case.name);
+                assert_eq!(result, expected, "{context}");
+                assert_eq!(resumed.outputs, upfront.outputs, "{context}");
+                assert_eq!(resumed.program, upfront.program, "{context}");
+            }
+        }
+    }
+}
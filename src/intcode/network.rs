@@ -0,0 +1,337 @@
+use std::{
+    fmt::{self, Display},
+    ops::ControlFlow,
+};
+
+use super::{execute, IntcodeError, IntcodeMachine, State};
+
+/// A machine faulted while running as part of a group.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkError {
+    pub node: usize,
+    pub error: IntcodeError,
+}
+
+impl Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "machine {}: {}", self.node, self.error)
+    }
+}
+
+impl std::error::Error for NetworkError {}
+
+/// How a group of machines stopped running.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// Every machine terminated.
+    Halted,
+    /// None of the machines could make progress; these ones are stuck waiting for input.
+    Deadlocked(Vec<usize>),
+    /// A routing hook asked to stop.
+    Stopped,
+}
+
+/// Runs a group of machines round-robin, each until it blocks on input or halts, handing
+/// what it printed to a routing hook that can feed it to the others.
+#[derive(Debug, Clone)]
+pub struct Scheduler {
+    pub machines: Vec<IntcodeMachine>,
+    states: Vec<State>,
+}
+
+impl Scheduler {
+    pub fn new(machines: Vec<IntcodeMachine>) -> Self {
+        Scheduler {
+            states: vec![State::Active; machines.len()],
+            machines,
+        }
+    }
+
+    /// Where each machine stopped on its last turn.
+    pub fn states(&self) -> &[State] {
+        &self.states
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.states.iter().all(|state| *state == State::Terminated)
+    }
+
+    /// Gives every machine that hasn't terminated one turn. After each turn, `route` gets
+    /// the machine's index, its outputs and all the machines to deliver them to. Returns
+    /// `None` if the machines should keep running.
+    pub fn round(
+        &mut self,
+        route: &mut impl FnMut(usize, Vec<i64>, &mut [IntcodeMachine]) -> ControlFlow<()>,
+    ) -> Result<Option<Outcome>, NetworkError> {
+        let mut progressed = false;
+
+        for node in 0..self.machines.len() {
+            if self.states[node] == State::Terminated {
+                continue;
+            }
+            let machine = &mut self.machines[node];
+            let (before, pending) = (machine.instruction_pointer, machine.inputs.len());
+            let state = execute(machine).map_err(|error| NetworkError { node, error })?;
+            let outputs = machine.outputs.drain(..).collect::<Vec<_>>();
+            // a machine in a loop can block at the same instruction it started from
+            progressed |= machine.instruction_pointer != before
+                || machine.inputs.len() != pending
+                || !outputs.is_empty()
+                || state != self.states[node];
+            self.states[node] = state;
+
+            if !outputs.is_empty() && route(node, outputs, &mut self.machines).is_break() {
+                return Ok(Some(Outcome::Stopped));
+            }
+        }
+
+        if self.is_halted() {
+            Ok(Some(Outcome::Halted))
+        } else if !progressed {
+            let waiting = (0..self.machines.len())
+                .filter(|node| self.states[*node] == State::WaitingForInput)
+                .collect();
+            Ok(Some(Outcome::Deadlocked(waiting)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Runs rounds until every machine has terminated, nothing can make progress any more
+    /// or `route` asks to stop.
+    pub fn run(
+        &mut self,
+        mut route: impl FnMut(usize, Vec<i64>, &mut [IntcodeMachine]) -> ControlFlow<()>,
+    ) -> Result<Outcome, NetworkError> {
+        loop {
+            if let Some(outcome) = self.round(&mut route)? {
+                return Ok(outcome);
+            }
+        }
+    }
+}
+
+/// A packet sent between machines on a [`Network`], output as three values in a row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Packet {
+    pub destination: i64,
+    pub x: i64,
+    pub y: i64,
+}
+
+/// Hooks for whatever sits outside the machines of a [`Network`], like a NAT.
+pub trait Nat {
+    /// Receives a packet sent to an address that doesn't belong to a machine. Break to
+    /// stop the network.
+    fn receive(&mut self, packet: Packet) -> ControlFlow<()>;
+
+    /// Called whenever the whole network is idle: nothing was sent and every machine was
+    /// left waiting on an empty queue. Continue with a packet to wake it back up, or break
+    /// to stop it.
+    fn idle(&mut self) -> ControlFlow<(), Packet>;
+
+    /// Sees every packet sent, before it's delivered.
+    fn monitor(&mut self, _packet: &Packet) {}
+}
+
+/// Machines running network interface programs: each is booted with its address, then
+/// exchanges packets with the others. A machine asking for input with nothing queued is
+/// fed the idle input, -1 unless changed.
+#[derive(Debug, Clone)]
+pub struct Network {
+    pub scheduler: Scheduler,
+    idle_input: Option<i64>,
+    partial: Vec<Vec<i64>>,
+}
+
+impl Network {
+    /// Boots `size` copies of the machine with addresses `0..size`.
+    pub fn new(machine: &IntcodeMachine, size: usize) -> Self {
+        let machines = (0..size)
+            .map(|address| {
+                let mut machine = machine.fork();
+                machine.inputs.push_back(address as i64);
+                machine
+            })
+            .collect();
+
+        Network {
+            scheduler: Scheduler::new(machines),
+            idle_input: Some(-1),
+            partial: vec![Vec::new(); size],
+        }
+    }
+
+    /// Sets what's fed to a machine waiting on an empty queue; `None` leaves it blocked.
+    pub fn with_idle_input(mut self, value: Option<i64>) -> Self {
+        self.idle_input = value;
+        self
+    }
+
+    /// Queues a packet for the machine it's addressed to. Returns `false` if there's no
+    /// such machine.
+    pub fn send(&mut self, packet: Packet) -> bool {
+        match usize::try_from(packet.destination)
+            .ok()
+            .and_then(|address| self.scheduler.machines.get_mut(address))
+        {
+            Some(machine) => {
+                machine.inputs.extend([packet.x, packet.y]);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Runs the network until every machine has halted or `nat` stops it.
+    pub fn run(&mut self, nat: &mut impl Nat) -> Result<Outcome, NetworkError> {
+        loop {
+            let mut starved = true;
+            for (machine, state) in self
+                .scheduler
+                .machines
+                .iter_mut()
+                .zip(self.scheduler.states.iter())
+            {
+                if *state == State::Terminated {
+                    continue;
+                }
+                if !machine.inputs.is_empty() {
+                    starved = false;
+                } else if let Some(value) = self.idle_input {
+                    machine.inputs.push_back(value);
+                }
+            }
+
+            let mut sent = false;
+            let partial = &mut self.partial;
+            let outcome = self.scheduler.round(&mut |from, outputs, machines| {
+                partial[from].extend(outputs);
+                let complete = partial[from].len() / 3 * 3;
+                for values in partial[from]
+                    .drain(..complete)
+                    .collect::<Vec<_>>()
+                    .chunks(3)
+                {
+                    let packet = Packet {
+                        destination: values[0],
+                        x: values[1],
+                        y: values[2],
+                    };
+                    sent = true;
+                    nat.monitor(&packet);
+                    match usize::try_from(packet.destination)
+                        .ok()
+                        .and_then(|address| machines.get_mut(address))
+                    {
+                        Some(machine) => machine.inputs.extend([packet.x, packet.y]),
+                        None => nat.receive(packet)?,
+                    }
+                }
+                ControlFlow::Continue(())
+            })?;
+
+            let idle = match outcome {
+                None => starved && !sent,
+                Some(Outcome::Deadlocked(_)) => true,
+                Some(outcome) => return Ok(outcome),
+            };
+            if idle {
+                match nat.idle() {
+                    ControlFlow::Continue(packet) => {
+                        nat.monitor(&packet);
+                        self.send(packet);
+                    }
+                    ControlFlow::Break(()) => return Ok(Outcome::Stopped),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{assemble, parse_machine};
+
+    // machine 0 sends the first packet, then every machine passes packets on to the next
+    // address with y incremented
+    const RELAY: &str = "
+                IN [addr]
+                JT [addr], #loop
+                OUT #1
+                OUT #0
+                OUT #100
+        loop:   IN [x]
+                EQ [x], #-1, [idle]
+                JT [idle], #loop
+                IN [y]
+                ADD [addr], #1, [dest]
+                ADD [y], #1, [y]
+                OUT [dest]
+                OUT [x]
+                OUT [y]
+                JT #1, #loop
+        addr:   .data 0
+        x:      .data 0
+        y:      .data 0
+        dest:   .data 0
+        idle:   .data 0
+    ";
+
+    struct Relay {
+        last: Option<Packet>,
+        received: Vec<i64>,
+        idles: usize,
+    }
+
+    impl Nat for Relay {
+        fn receive(&mut self, packet: Packet) -> ControlFlow<()> {
+            self.received.push(packet.y);
+            self.last = Some(packet);
+            ControlFlow::Continue(())
+        }
+
+        fn idle(&mut self) -> ControlFlow<(), Packet> {
+            self.idles += 1;
+            match self.last {
+                Some(packet) if self.idles < 3 => ControlFlow::Continue(Packet {
+                    destination: 0,
+                    ..packet
+                }),
+                _ => ControlFlow::Break(()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_network() {
+        let machine = IntcodeMachine::from_program(assemble(RELAY).unwrap());
+        let mut network = Network::new(&machine, 3);
+        let mut nat = Relay {
+            last: None,
+            received: Vec::new(),
+            idles: 0,
+        };
+
+        assert_eq!(network.run(&mut nat), Ok(Outcome::Stopped));
+        assert_eq!(nat.received, [102, 105, 108]);
+        assert_eq!(nat.idles, 3);
+    }
+
+    #[test]
+    fn test_deadlock() {
+        // each machine waits for the other before saying anything
+        let (_, machine) = parse_machine("3,9,4,9,99").unwrap();
+        let mut scheduler = Scheduler::new(vec![machine.fork(), machine.fork()]);
+        let outcome = scheduler.run(|_, _, _| ControlFlow::Continue(()));
+        assert_eq!(outcome, Ok(Outcome::Deadlocked(vec![0, 1])));
+
+        scheduler.machines[1].inputs.push_back(7);
+        let outcome = scheduler.run(|from, outputs, machines| {
+            machines[1 - from].inputs.extend(outputs);
+            ControlFlow::Continue(())
+        });
+        assert_eq!(outcome, Ok(Outcome::Halted));
+    }
+}
@@ -0,0 +1,237 @@
+use std::ops::RangeInclusive;
+
+use super::{execute, run_batch, IntcodeMachine, MemoryLimitExceeded, State};
+
+impl IntcodeMachine {
+    /// Overwrites cells of the program before it runs, e.g. day 2's noun and verb. Fails
+    /// if a patch lands past the memory limit.
+    pub fn with_patches(mut self, patches: &[(usize, i64)]) -> Result<Self, MemoryLimitExceeded> {
+        for (address, value) in patches.iter() {
+            self.program.write(*address, *value)?;
+        }
+        Ok(self)
+    }
+}
+
+/// Runs the machine with the patches applied, returning what `output` reads from it once
+/// it halts. Patches past the memory limit, faults and blocking on input give `None`.
+pub fn evaluate_patched(
+    machine: IntcodeMachine,
+    patches: &[(usize, i64)],
+    output: impl Fn(&IntcodeMachine) -> i64,
+) -> Option<i64> {
+    let mut trial = machine.with_patches(patches).ok()?;
+    match execute(&mut trial) {
+        Ok(State::Terminated) => Some(output(&trial)),
+        _ => None,
+    }
+}
+
+/// An output that depends linearly on the noun and the verb.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Affine {
+    pub constant: i64,
+    pub noun: i64,
+    pub verb: i64,
+}
+
+impl Affine {
+    /// The output at `noun` and `verb`, or `None` if it overflows an `i64`.
+    pub fn at(&self, noun: i64, verb: i64) -> Option<i64> {
+        self.constant
+            .checked_add(self.noun.checked_mul(noun)?)?
+            .checked_add(self.verb.checked_mul(verb)?)
+    }
+
+    /// The first noun and verb in `values` giving `target`, ordered by noun then verb.
+    pub fn solve(&self, target: i64, values: &RangeInclusive<i64>) -> Option<(i64, i64)> {
+        values.clone().find_map(|noun| {
+            let rest = target
+                .checked_sub(self.constant)?
+                .checked_sub(self.noun.checked_mul(noun)?)?;
+            match self.verb {
+                0 => (rest == 0).then_some((noun, *values.start())),
+                verb => {
+                    let verb_value = rest.checked_div(verb)?;
+                    (rest % verb == 0 && values.contains(&verb_value)).then_some((noun, verb_value))
+                }
+            }
+        })
+    }
+}
+
+/// The noun and verb to patch in at `addresses`, searching `values` for both, that make
+/// `output` read `target` once the machine halts. Answers are ordered by noun then verb.
+pub struct NounVerbSearch<F> {
+    pub addresses: (usize, usize),
+    pub values: RangeInclusive<i64>,
+    pub output: F,
+}
+
+impl<F: Fn(&IntcodeMachine) -> i64 + Sync> NounVerbSearch<F> {
+    /// Searches for day 2's noun and verb at addresses 1 and 2.
+    pub fn new(values: RangeInclusive<i64>, output: F) -> Self {
+        NounVerbSearch {
+            addresses: (1, 2),
+            values,
+            output,
+        }
+    }
+
+    fn evaluate(&self, machine: IntcodeMachine, noun: i64, verb: i64) -> Option<i64> {
+        let (noun_address, verb_address) = self.addresses;
+        evaluate_patched(
+            machine,
+            &[(noun_address, noun), (verb_address, verb)],
+            &self.output,
+        )
+    }
+
+    /// Fits the output to an affine function of the noun and verb from three runs, then
+    /// checks the fit against the corners and middle of the search space. This is a
+    /// heuristic: an output that's only affine at the points sampled fools it. A fit that
+    /// overflows an `i64` anywhere it's checked counts as no fit.
+    pub fn fit(&self, machine: &IntcodeMachine) -> Option<Affine> {
+        let (low, high) = (*self.values.start(), *self.values.end());
+        if low >= high {
+            return None;
+        }
+
+        let origin = self.evaluate(machine.fork(), low, low)?;
+        let affine = Affine {
+            noun: self
+                .evaluate(machine.fork(), low + 1, low)?
+                .checked_sub(origin)?,
+            verb: self
+                .evaluate(machine.fork(), low, low + 1)?
+                .checked_sub(origin)?,
+            constant: 0,
+        };
+        let affine = Affine {
+            constant: origin.checked_sub(affine.at(low, low)?)?,
+            ..affine
+        };
+
+        // the midpoint always fits, but the distance between the bounds may not
+        let middle = ((i128::from(low) + i128::from(high)) / 2) as i64;
+        let checks = [(high, low), (low, high), (high, high), (middle, middle + 1)];
+        checks
+            .iter()
+            .all(|(noun, verb)| {
+                affine
+                    .at(*noun, *verb)
+                    .is_some_and(|value| self.evaluate(machine.fork(), *noun, *verb) == Some(value))
+            })
+            .then_some(affine)
+    }
+
+    /// Solves for the target directly when the output is affine, otherwise tries pairs in
+    /// order, one noun at a time with its verbs spread across all cores, stopping at the
+    /// first noun with an answer. Since the fit is only a heuristic, finding no solution
+    /// or one the program disagrees with falls back to trying pairs too.
+    pub fn find(&self, machine: &IntcodeMachine, target: i64) -> Option<(i64, i64)> {
+        let solved = self
+            .fit(machine)
+            .and_then(|affine| affine.solve(target, &self.values));
+        if let Some((noun, verb)) = solved {
+            if self.evaluate(machine.fork(), noun, verb) == Some(target) {
+                return solved;
+            }
+        }
+
+        let verbs = self.values.clone().collect::<Vec<_>>();
+        self.values.clone().find_map(|noun| {
+            let found = run_batch(machine, &verbs, |trial, verb| {
+                self.evaluate(trial, noun, *verb) == Some(target)
+            });
+            let verb = found.iter().position(|found| *found)?;
+            Some((noun, verbs[verb]))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{parse_machine, PAGE_SIZE};
+
+    #[test]
+    fn test_affine() {
+        // [0] = 100 * noun + verb, once the first instruction has read both as addresses
+        let (_, machine) = parse_machine("1,0,0,3,2,1,14,3,1,3,2,0,99,0,100").unwrap();
+        let search = NounVerbSearch::new(0..=14, |machine: &IntcodeMachine| machine.program[0]);
+
+        assert_eq!(
+            search.fit(&machine),
+            Some(Affine {
+                constant: 0,
+                noun: 100,
+                verb: 1
+            })
+        );
+        assert_eq!(search.find(&machine, 1207), Some((12, 7)));
+        assert_eq!(search.find(&machine, 1215), None);
+    }
+
+    #[test]
+    fn test_brute_force() {
+        // [0] = [noun] * [verb]
+        let (_, machine) = parse_machine("2,0,0,0,99,3,5,7").unwrap();
+        let search = NounVerbSearch::new(0..=7, |machine: &IntcodeMachine| machine.program[0]);
+
+        assert_eq!(search.fit(&machine), None);
+        assert_eq!(search.find(&machine, 35), Some((6, 7)));
+        assert_eq!(search.find(&machine, 11), None);
+    }
+
+    #[test]
+    fn test_affine_at_samples_only() {
+        // [0] = 100 * noun + verb, plus 7 when the noun is 3
+        let (_, machine) = parse_machine(
+            "1101,0,0,25,1002,1,99,26,1,25,26,27,1008,1,3,28,1002,28,7,28,1,27,28,0,99,0,0,0,0",
+        )
+        .unwrap();
+        let search = NounVerbSearch::new(0..=14, |machine: &IntcodeMachine| machine.program[0]);
+
+        assert!(search.fit(&machine).is_some());
+        assert_eq!(search.find(&machine, 321), Some((3, 14)));
+    }
+
+    #[test]
+    fn test_fit_full_range() {
+        // [1] = noun, so the fit samples both ends of the i64 range
+        let (_, machine) = parse_machine("99,0,0").unwrap();
+        let search = NounVerbSearch::new(i64::MIN..=i64::MAX, |machine: &IntcodeMachine| {
+            machine.program[1]
+        });
+
+        assert_eq!(
+            search.fit(&machine),
+            Some(Affine {
+                constant: 0,
+                noun: 1,
+                verb: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_patch_past_limit() {
+        let (_, machine) = parse_machine("99").unwrap();
+        let machine = machine.with_memory_limit(PAGE_SIZE);
+
+        assert!(machine.clone().with_patches(&[(PAGE_SIZE, 1)]).is_err());
+        assert_eq!(machine.with_patches(&[(0, 1)]).unwrap().program[0], 1);
+    }
+
+    #[test]
+    fn test_overflow() {
+        let affine = Affine {
+            constant: i64::MIN,
+            noun: i64::MAX,
+            verb: 1,
+        };
+        assert_eq!(affine.at(2, 0), None);
+        assert_eq!(affine.solve(1, &(0..=3)), None);
+    }
+}
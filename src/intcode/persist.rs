@@ -0,0 +1,275 @@
+use tinyjson::JsonValue;
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+    str::FromStr,
+};
+
+use num::BigInt;
+
+use super::{Arithmetic, IntcodeMachine, Memory, MemoryModel};
+
+/// Bumped whenever the layout of saved machines changes. Version 1 predates arithmetic
+/// policies and wide cells, and still loads as [`Arithmetic::Checked`].
+pub const STATE_VERSION: f64 = 2.0;
+
+// numbers are stored as strings, JSON numbers are `f64`s and would lose precision
+fn numbers(values: impl IntoIterator<Item = i64>) -> JsonValue {
+    JsonValue::Array(
+        values
+            .into_iter()
+            .map(|value| JsonValue::String(value.to_string()))
+            .collect(),
+    )
+}
+
+fn number<T: FromStr>(value: &JsonValue, key: &str) -> Result<T, String> {
+    value
+        .get::<String>()
+        .and_then(|value| value.parse().ok())
+        .ok_or(format!("expected `{key}` to be a number in a string."))
+}
+
+fn list<T: FromStr>(json: &HashMap<String, JsonValue>, key: &str) -> Result<Vec<T>, String> {
+    json.get(key)
+        .and_then(|value| value.get::<Vec<JsonValue>>())
+        .ok_or(format!("expected `{key}` to be an array."))?
+        .iter()
+        .map(|value| number(value, key))
+        .collect()
+}
+
+fn field<'a>(json: &'a HashMap<String, JsonValue>, key: &str) -> Result<&'a JsonValue, String> {
+    json.get(key).ok_or(format!("expected key `{key}`."))
+}
+
+impl From<&IntcodeMachine> for JsonValue {
+    fn from(machine: &IntcodeMachine) -> Self {
+        let mut map: HashMap<String, JsonValue> = HashMap::new();
+        let model = match machine.program.model() {
+            MemoryModel::Dense => "dense",
+            MemoryModel::Paged => "paged",
+            MemoryModel::Adaptive => "adaptive",
+        };
+        let paged = machine.program.paged_cells();
+
+        map.insert("version".into(), JsonValue::Number(STATE_VERSION));
+        map.insert("program".into(), numbers(machine.program.to_vec()));
+        map.insert(
+            "paged_addresses".into(),
+            numbers(paged.iter().map(|(address, _)| *address as i64)),
+        );
+        map.insert(
+            "paged_values".into(),
+            numbers(paged.iter().map(|(_, value)| *value)),
+        );
+        let wide = machine.program.wide_cells();
+        map.insert(
+            "wide_addresses".into(),
+            numbers(wide.iter().map(|(address, _)| *address as i64)),
+        );
+        map.insert(
+            "wide_values".into(),
+            JsonValue::Array(
+                wide.iter()
+                    .map(|(_, value)| JsonValue::String(value.to_string()))
+                    .collect(),
+            ),
+        );
+        map.insert("memory_model".into(), JsonValue::String(model.into()));
+        map.insert(
+            "memory_limit".into(),
+            JsonValue::String(machine.program.limit().to_string()),
+        );
+        map.insert(
+            "instruction_pointer".into(),
+            JsonValue::String(machine.instruction_pointer.to_string()),
+        );
+        map.insert(
+            "relative_base".into(),
+            JsonValue::String(machine.relative_base.to_string()),
+        );
+        let arithmetic = match machine.arithmetic {
+            Arithmetic::Checked => "checked",
+            Arithmetic::Wrapping => "wrapping",
+            Arithmetic::Arbitrary => "arbitrary",
+        };
+        map.insert("arithmetic".into(), JsonValue::String(arithmetic.into()));
+        map.insert("inputs".into(), numbers(machine.inputs.iter().copied()));
+        map.insert("outputs".into(), numbers(machine.outputs.iter().copied()));
+
+        JsonValue::Object(map)
+    }
+}
+
+impl TryFrom<&JsonValue> for IntcodeMachine {
+    type Error = String;
+
+    fn try_from(value: &JsonValue) -> Result<Self, Self::Error> {
+        let json = value
+            .get::<HashMap<String, JsonValue>>()
+            .ok_or("expected machine to be a JSON object.")?;
+
+        let version = field(json, "version")?
+            .get::<f64>()
+            .ok_or("expected `version` to be a number.")?;
+        if *version != STATE_VERSION && *version != 1.0 {
+            return Err(format!("unsupported machine version {version}."));
+        }
+
+        let model = match field(json, "memory_model")?
+            .get::<String>()
+            .map(String::as_str)
+        {
+            Some("dense") => MemoryModel::Dense,
+            Some("paged") => MemoryModel::Paged,
+            Some("adaptive") => MemoryModel::Adaptive,
+            _ => return Err("expected `memory_model` to be dense, paged or adaptive.".into()),
+        };
+        let limit = number(field(json, "memory_limit")?, "memory_limit")?;
+        let mut program = Memory::from(list(json, "program")?)
+            .with_model(model)
+            .with_limit(limit);
+
+        let addresses = list::<usize>(json, "paged_addresses")?;
+        let values = list(json, "paged_values")?;
+        if addresses.len() != values.len() {
+            return Err("expected as many paged values as paged addresses.".into());
+        }
+        for (address, value) in addresses.into_iter().zip(values) {
+            program
+                .write(address, value)
+                .map_err(|e| format!("paged address {} exceeds the memory limit.", e.address))?;
+        }
+
+        let mut arithmetic = Arithmetic::default();
+        if *version >= 2.0 {
+            let addresses = list::<usize>(json, "wide_addresses")?;
+            let values = list::<BigInt>(json, "wide_values")?;
+            if addresses.len() != values.len() {
+                return Err("expected as many wide values as wide addresses.".into());
+            }
+            for (address, value) in addresses.into_iter().zip(values) {
+                program
+                    .write_big(address, value)
+                    .map_err(|e| format!("wide address {} exceeds the memory limit.", e.address))?;
+            }
+
+            arithmetic = match field(json, "arithmetic")?
+                .get::<String>()
+                .map(String::as_str)
+            {
+                Some("checked") => Arithmetic::Checked,
+                Some("wrapping") => Arithmetic::Wrapping,
+                Some("arbitrary") => Arithmetic::Arbitrary,
+                _ => {
+                    return Err(
+                        "expected `arithmetic` to be checked, wrapping or arbitrary.".into(),
+                    )
+                }
+            };
+        }
+
+        Ok(IntcodeMachine {
+            program,
+            instruction_pointer: number(
+                field(json, "instruction_pointer")?,
+                "instruction_pointer",
+            )?,
+            relative_base: number(field(json, "relative_base")?, "relative_base")?,
+            inputs: VecDeque::from(list(json, "inputs")?),
+            outputs: VecDeque::from(list(json, "outputs")?),
+            arithmetic,
+        })
+    }
+}
+
+impl IntcodeMachine {
+    /// Writes the machine's full state to a versioned JSON file, to pick up later with
+    /// [`load`](IntcodeMachine::load).
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let json = JsonValue::from(self);
+        let mut file = fs::File::create(path)?;
+        json.format_to(&mut file)
+    }
+
+    /// Restores a machine written by [`save`](IntcodeMachine::save).
+    pub fn load(path: impl AsRef<Path>) -> io::Result<IntcodeMachine> {
+        let invalid = |e: String| io::Error::new(ErrorKind::InvalidData, e);
+        let json =
+            JsonValue::from_str(&fs::read_to_string(path)?).map_err(|e| invalid(e.to_string()))?;
+        IntcodeMachine::try_from(&json).map_err(invalid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{execute, parse_machine, State};
+
+    #[test]
+    fn test_round_trip() {
+        // waits for input after storing a value too big for an f64 far past the program
+        let (_, mut machine) =
+            parse_machine("109,7,21101,9007199254740993,0,50000,104,-1,3,0,99").unwrap();
+        assert_eq!(execute(&mut machine), Ok(State::WaitingForInput));
+        machine.inputs.extend([-2, 3]);
+        assert_eq!(execute(&mut machine), Ok(State::Terminated));
+        machine.inputs.push_back(i64::MIN);
+
+        let json = JsonValue::from(&machine);
+        let restored = IntcodeMachine::try_from(&json).unwrap();
+        assert_eq!(restored.program, machine.program);
+        assert_eq!(restored.program.read(50007), 9007199254740993);
+        assert_eq!(restored.instruction_pointer, machine.instruction_pointer);
+        assert_eq!(restored.relative_base, 7);
+        assert_eq!(restored.inputs, machine.inputs);
+        assert_eq!(restored.outputs, [-1]);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let (_, mut machine) = parse_machine("3,5,4,5,99,0").unwrap();
+        assert_eq!(execute(&mut machine), Ok(State::WaitingForInput));
+
+        let path = std::env::temp_dir().join(format!("intcode-{}.json", std::process::id()));
+        machine.save(&path).unwrap();
+        let mut restored = IntcodeMachine::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        restored.inputs.push_back(42);
+        assert_eq!(execute(&mut restored), Ok(State::Terminated));
+        assert_eq!(restored.outputs, [42]);
+
+        let mut json = JsonValue::from(&machine);
+        json["version"] = JsonValue::Number(STATE_VERSION + 1.0);
+        assert!(IntcodeMachine::try_from(&json).is_err());
+    }
+
+    #[test]
+    fn test_wide_cells() {
+        let (_, machine) = parse_machine("1102,9223372036854775807,3,0,99").unwrap();
+        let mut machine = machine.with_arithmetic(Arithmetic::Arbitrary);
+        assert_eq!(execute(&mut machine), Ok(State::Terminated));
+
+        let json = JsonValue::from(&machine);
+        let restored = IntcodeMachine::try_from(&json).unwrap();
+        assert_eq!(restored.arithmetic, Arithmetic::Arbitrary);
+        assert_eq!(restored.program, machine.program);
+        assert_eq!(restored.program.read_big(0), BigInt::from(i64::MAX) * 3);
+
+        // older saves have neither, and load as checked
+        let mut json = JsonValue::from(&parse_machine("99").unwrap().1);
+        json["version"] = JsonValue::Number(1.0);
+        if let JsonValue::Object(map) = &mut json {
+            for key in ["wide_addresses", "wide_values", "arithmetic"] {
+                map.remove(key);
+            }
+        }
+        let restored = IntcodeMachine::try_from(&json).unwrap();
+        assert_eq!(restored.arithmetic, Arithmetic::Checked);
+    }
+}
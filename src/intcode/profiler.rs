@@ -0,0 +1,372 @@
+use enum_iterator::all;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Display},
+    io::{self, Write},
+    ops::Range,
+};
+
+use super::{fetch, step, IntcodeError, IntcodeMachine, Mode, Opcode, State, Stepper};
+
+const DEFAULT_RANGE_SIZE: usize = 64;
+
+/// Instructions spent in a function found through the store-then-jump call convention.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FunctionProfile {
+    /// Address the calls to the function jump to.
+    pub entry: usize,
+    pub calls: u64,
+    /// Instructions executed in the function itself.
+    pub own: u64,
+    /// Instructions executed in the function and everything it called.
+    pub total: u64,
+}
+
+/// A call stack, interned as the stack it was called from plus the function on top.
+#[derive(Debug, Clone, Copy)]
+struct Stack {
+    parent: usize,
+    entry: usize,
+    count: u64,
+}
+
+/// Counts executed instructions per opcode, per address range and per function. Use
+/// its [`Stepper::execute`] in place of [`execute`](super::execute) to opt in.
+///
+/// Functions are found through the calling convention the disassembler follows too: a
+/// call stores its return address with an `ADD` or `MUL` of two constants, then jumps
+/// unconditionally, and the function returns by jumping back to that address. Time is
+/// measured in instructions executed, which keeps profiles deterministic.
+#[derive(Debug, Clone)]
+pub struct Profiler {
+    pub instructions: u64,
+    range_size: usize,
+    by_opcode: HashMap<Opcode, u64>,
+    by_range: BTreeMap<usize, u64>,
+    /// Open calls as their return address and the stack they were made from.
+    frames: Vec<(usize, usize)>,
+    /// Every call stack seen so far, the root stack first.
+    stacks: Vec<Stack>,
+    stack_ids: HashMap<(usize, usize), usize>,
+    current: usize,
+    /// Return address stored by the previous instruction, if it could start a call.
+    pending_return: Option<usize>,
+    calls: HashMap<usize, u64>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Profiler::new()
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler {
+            instructions: 0,
+            range_size: DEFAULT_RANGE_SIZE,
+            by_opcode: HashMap::new(),
+            by_range: BTreeMap::new(),
+            frames: Vec::new(),
+            stacks: vec![Stack {
+                parent: 0,
+                entry: 0,
+                count: 0,
+            }],
+            stack_ids: HashMap::new(),
+            current: 0,
+            pending_return: None,
+            calls: HashMap::new(),
+        }
+    }
+
+    /// Groups addresses into ranges of `size` cells rather than the default 64.
+    pub fn with_range_size(mut self, size: usize) -> Self {
+        self.range_size = size.max(1);
+        self
+    }
+
+    pub fn count(&self, opcode: Opcode) -> u64 {
+        self.by_opcode.get(&opcode).copied().unwrap_or_default()
+    }
+
+    /// Instruction counts for every address range that executed anything, in order.
+    pub fn ranges(&self) -> Vec<(Range<usize>, u64)> {
+        self.by_range
+            .iter()
+            .map(|(start, count)| (*start..start + self.range_size, *count))
+            .collect()
+    }
+
+    /// Every function called so far, those that took longest overall first.
+    pub fn functions(&self) -> Vec<FunctionProfile> {
+        let mut functions = HashMap::<usize, FunctionProfile>::new();
+        for (id, stack) in self.stacks.iter().enumerate().skip(1) {
+            functions.entry(stack.entry).or_default().own += stack.count;
+            // recursive functions only count once per stack
+            let mut seen = self.frames_of(id);
+            seen.sort_unstable();
+            seen.dedup();
+            for entry in seen {
+                functions.entry(entry).or_default().total += stack.count;
+            }
+        }
+
+        let mut functions = functions
+            .into_iter()
+            .map(|(entry, profile)| FunctionProfile {
+                entry,
+                calls: self.calls[&entry],
+                ..profile
+            })
+            .collect::<Vec<_>>();
+        functions.sort_unstable_by_key(|f| (u64::MAX - f.total, f.entry));
+        functions
+    }
+
+    /// Writes the call stacks in the folded format flamegraph tools take, one line per
+    /// stack with its frames separated by semicolons followed by its instruction count.
+    pub fn write_folded(&self, mut writer: impl Write) -> io::Result<()> {
+        let mut lines = self
+            .stacks
+            .iter()
+            .enumerate()
+            .filter(|(_, stack)| stack.count > 0)
+            .map(|(id, stack)| {
+                let frames = self.frames_of(id).into_iter();
+                let frames = frames.map(|entry| format!(";fn_{entry:04}"));
+                (format!("main{}", frames.collect::<String>()), stack.count)
+            })
+            .collect::<Vec<_>>();
+        lines.sort_unstable();
+        for (stack, count) in lines {
+            writeln!(writer, "{stack} {count}")?;
+        }
+        Ok(())
+    }
+
+    /// The functions on a stack, outermost first.
+    fn frames_of(&self, mut id: usize) -> Vec<usize> {
+        let mut frames = Vec::new();
+        while id != 0 {
+            frames.push(self.stacks[id].entry);
+            id = self.stacks[id].parent;
+        }
+        frames.reverse();
+        frames
+    }
+
+    fn call(&mut self, entry: usize, return_address: usize) {
+        *self.calls.entry(entry).or_default() += 1;
+        let parent = self.current;
+        self.frames.push((return_address, parent));
+        let next = self.stacks.len();
+        self.current = *self.stack_ids.entry((parent, entry)).or_insert(next);
+        if self.current == next {
+            self.stacks.push(Stack {
+                parent,
+                entry,
+                count: 0,
+            });
+        }
+    }
+
+    /// Returns from the innermost call expecting to return to `target`, if any.
+    fn jump(&mut self, target: usize) {
+        if let Some(depth) = self.frames.iter().rposition(|(back, _)| *back == target) {
+            self.current = self.frames[depth].1;
+            self.frames.truncate(depth);
+        }
+    }
+}
+
+impl Stepper for Profiler {
+    /// Executes a single instruction, counting it. Blocking on input counts nothing.
+    fn step(&mut self, machine: &mut IntcodeMachine) -> Result<State, IntcodeError> {
+        let ip = machine.instruction_pointer;
+        // the instruction may overwrite itself
+        let instruction = fetch(machine).ok();
+        let state = step(machine)?;
+        if state == State::WaitingForInput {
+            return Ok(state);
+        }
+
+        *self.by_range.entry(ip - ip % self.range_size).or_default() += 1;
+        self.instructions += 1;
+        // a call counts towards the caller, a return towards the function returning
+        self.stacks[self.current].count += 1;
+        // values too big for an `i64` decode differently at full precision
+        let Some(instruction) = instruction else {
+            return Ok(state);
+        };
+        *self.by_opcode.entry(instruction.opcode).or_default() += 1;
+
+        let next = ip + instruction.opcode.length();
+        let pending_return = self.pending_return.take();
+        match instruction.opcode {
+            Opcode::Addition | Opcode::Multiplication => {
+                let identity = (instruction.opcode == Opcode::Multiplication) as i64;
+                let [a, b, _] = instruction.parameters;
+                let constant = match instruction.modes {
+                    [Mode::Immediate, Mode::Immediate, _] if b == identity => Some(a),
+                    [Mode::Immediate, Mode::Immediate, _] if a == identity => Some(b),
+                    _ => None,
+                };
+                // the return address skips the jump that follows
+                self.pending_return = constant
+                    .and_then(|constant| usize::try_from(constant).ok())
+                    .filter(|back| *back == next + 3);
+            }
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse if machine.instruction_pointer != next => {
+                let target = machine.instruction_pointer;
+                match pending_return {
+                    Some(back) if back == next => self.call(target, next),
+                    _ => self.jump(target),
+                }
+            }
+            _ => {}
+        }
+
+        Ok(state)
+    }
+}
+
+impl Display for Profiler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let share = |count: u64| 100.0 * count as f64 / self.instructions.max(1) as f64;
+
+        writeln!(f, "{} instructions", self.instructions)?;
+        writeln!(f, "\nby opcode:")?;
+        for opcode in all::<Opcode>().filter(|opcode| self.count(*opcode) > 0) {
+            let count = self.count(opcode);
+            writeln!(
+                f,
+                "  {:<4}{count:>12} {:>6.2}%",
+                opcode.mnemonic(),
+                share(count)
+            )?;
+        }
+
+        writeln!(f, "\nby address:")?;
+        for (range, count) in self.ranges() {
+            writeln!(
+                f,
+                "  {:04}..{:04}{count:>12} {:>6.2}%",
+                range.start,
+                range.end,
+                share(count)
+            )?;
+        }
+
+        let functions = self.functions();
+        if !functions.is_empty() {
+            writeln!(f, "\nby function:")?;
+            for function in functions {
+                writeln!(
+                    f,
+                    "  fn_{:04} {:>8} calls {:>12} own {:>12} total {:>6.2}%",
+                    function.entry,
+                    function.calls,
+                    function.own,
+                    function.total,
+                    share(function.total)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{assemble, parse_machine};
+
+    #[test]
+    fn test_counts() {
+        // count down from 3
+        let (_, mut machine) = parse_machine("1101,3,0,12,1001,12,-1,12,1005,12,4,99,0").unwrap();
+        let mut profiler = Profiler::new().with_range_size(8);
+        assert_eq!(profiler.execute(&mut machine), Ok(State::Terminated));
+
+        assert_eq!(profiler.instructions, 8);
+        assert_eq!(profiler.count(Opcode::Addition), 4);
+        assert_eq!(profiler.count(Opcode::JumpIfTrue), 3);
+        assert_eq!(profiler.count(Opcode::Halt), 1);
+        assert_eq!(profiler.ranges(), [(0..8, 4), (8..16, 4)]);
+        assert!(profiler.functions().is_empty());
+
+        let mut folded = Vec::new();
+        profiler.write_folded(&mut folded).unwrap();
+        assert_eq!(String::from_utf8(folded).unwrap(), "main 8\n");
+    }
+
+    #[test]
+    fn test_functions() {
+        // calls `double` twice, which calls `inc` twice
+        let program = assemble(
+            "
+                    ARB #stack
+                    ADD #back1, #0, rb
+                    JT #1, #double
+            back1:  ADD #back2, #0, rb
+                    JT #1, #double
+            back2:  HLT
+            double: ARB #1
+                    ADD #back3, #0, rb
+                    JT #1, #inc
+            back3:  ADD #back4, #0, rb
+                    JT #1, #inc
+            back4:  ARB #-1
+                    JT #1, rb
+            inc:    ARB #1
+                    ARB #-1
+                    JT #1, rb
+            stack:  .data 0
+            ",
+        )
+        .unwrap();
+        let mut machine = IntcodeMachine::from_program(program);
+        let mut profiler = Profiler::new();
+        assert_eq!(profiler.execute(&mut machine), Ok(State::Terminated));
+
+        assert_eq!(profiler.instructions, 32);
+        assert_eq!(
+            profiler.functions(),
+            [
+                FunctionProfile {
+                    entry: 17,
+                    calls: 2,
+                    own: 14,
+                    total: 26
+                },
+                FunctionProfile {
+                    entry: 38,
+                    calls: 4,
+                    own: 12,
+                    total: 12
+                }
+            ]
+        );
+
+        let mut folded = Vec::new();
+        profiler.write_folded(&mut folded).unwrap();
+        assert_eq!(
+            String::from_utf8(folded).unwrap(),
+            "main 6\nmain;fn_0017 14\nmain;fn_0017;fn_0038 12\n"
+        );
+    }
+
+    #[test]
+    fn test_relative_base_alone_is_no_call() {
+        // the quine from day 9 raises the relative base on every output
+        let (_, mut machine) =
+            parse_machine("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99").unwrap();
+        let mut profiler = Profiler::new();
+        assert_eq!(profiler.execute(&mut machine), Ok(State::Terminated));
+
+        assert!(profiler.functions().is_empty());
+        assert_eq!(profiler.stacks.len(), 1);
+    }
+}
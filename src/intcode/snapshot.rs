@@ -0,0 +1,79 @@
+use super::IntcodeMachine;
+
+/// A frozen copy of a machine's state that can be restored or forked from any number of
+/// times. It shares memory pages with the machine it was taken from, and taking or
+/// forking one costs the same as [`IntcodeMachine::fork`].
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    machine: IntcodeMachine,
+}
+
+impl Snapshot {
+    /// Starts a new machine from this snapshot.
+    pub fn fork(&self) -> IntcodeMachine {
+        self.machine.clone()
+    }
+}
+
+impl IntcodeMachine {
+    /// Returns an independent copy of the machine. Memory is shared copy-on-write, so only
+    /// the pages either machine writes to afterwards get duplicated.
+    ///
+    /// Sharing the page table is a reference count bump, so forking takes constant time
+    /// apart from copying any pending inputs and outputs.
+    pub fn fork(&self) -> IntcodeMachine {
+        self.clone()
+    }
+
+    /// Captures the machine's current state, including pending inputs and outputs.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            machine: self.clone(),
+        }
+    }
+
+    /// Rewinds the machine to a previously taken snapshot.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.clone_from(&snapshot.machine);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intcode::{execute, parse_machine, State, PAGE_SIZE};
+
+    #[test]
+    fn test_fork() {
+        // count down from 3, then store a value far past the program
+        let (_, mut machine) =
+            parse_machine("3,100,1001,100,-1,100,1005,100,2,1101,7,0,3000,99").unwrap();
+        assert_eq!(execute(&mut machine), Ok(State::WaitingForInput));
+
+        let mut fork = machine.fork();
+        assert_eq!(fork.program.shared_pages(&machine.program), 1);
+        fork.inputs.push_back(3);
+        assert_eq!(execute(&mut fork), Ok(State::Terminated));
+        assert_eq!(fork.program[3000], 7);
+        assert_eq!(fork.program.allocated(), 3 * PAGE_SIZE);
+
+        // the original is untouched and can still be resumed
+        assert_eq!(machine.program[3000], 0);
+        assert_eq!(machine.program.allocated(), PAGE_SIZE);
+        assert_eq!(machine.instruction_pointer, 0);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let (_, mut machine) = parse_machine("3,9,4,9,3,9,4,9,99,0").unwrap();
+        execute(&mut machine).unwrap();
+        let snapshot = machine.snapshot();
+
+        for value in [1, 2] {
+            machine.restore(&snapshot);
+            machine.inputs.extend([value, value * 10]);
+            assert_eq!(execute(&mut machine), Ok(State::Terminated));
+            assert_eq!(machine.outputs, [value, value * 10]);
+        }
+        assert_eq!(snapshot.fork().program[9], 0);
+    }
+}
@@ -0,0 +1,132 @@
+use std::{
+    num::NonZeroUsize,
+    sync::mpsc::{Receiver, Sender},
+    thread::{self, JoinHandle},
+};
+
+use super::{execute_with_io, IntcodeError, IntcodeIo, IntcodeMachine, State};
+
+/// Feeds a machine from one channel and copies its outputs into several, keeping a record
+/// of everything it sent.
+struct Endpoint {
+    receiver: Receiver<i64>,
+    senders: Vec<Sender<i64>>,
+    sent: Vec<i64>,
+}
+
+impl IntcodeIo for Endpoint {
+    fn input(&mut self) -> Option<i64> {
+        self.receiver.recv().ok()
+    }
+
+    fn output(&mut self, value: i64) {
+        // a peer that has already finished doesn't need to hear about it
+        for sender in self.senders.iter() {
+            let _ = sender.send(value);
+        }
+        self.sent.push(value);
+    }
+}
+
+/// Runs the machine on its own thread. Inputs already queued on the machine come first,
+/// then it blocks reading `receiver`; every output is sent to each of `senders`.
+///
+/// The thread finishes when the machine halts or faults, or when it needs input and every
+/// sender feeding `receiver` has been dropped, e.g. because the machines upstream halted.
+/// It hands back the machine with everything it printed in its `outputs`.
+pub fn spawn(
+    mut machine: IntcodeMachine,
+    receiver: Receiver<i64>,
+    senders: Vec<Sender<i64>>,
+) -> JoinHandle<(IntcodeMachine, Result<State, IntcodeError>)> {
+    thread::spawn(move || {
+        let mut endpoint = Endpoint {
+            receiver,
+            senders,
+            sent: Vec::new(),
+        };
+        let result = execute_with_io(&mut machine, &mut endpoint);
+        machine.outputs.extend(endpoint.sent);
+        (machine, result)
+    })
+}
+
+/// Calls `job` with a fork of `machine` for every parameter, spreading the calls across
+/// all available cores. Results are returned in the order of `parameters`.
+pub fn run_batch<P, T>(
+    machine: &IntcodeMachine,
+    parameters: &[P],
+    job: impl Fn(IntcodeMachine, &P) -> T + Sync,
+) -> Vec<T>
+where
+    P: Sync,
+    T: Send,
+{
+    let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    let chunk_size = parameters.len().div_ceil(threads).max(1);
+    let job = &job;
+
+    thread::scope(|scope| {
+        let workers = parameters
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|parameter| job(machine.fork(), parameter))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("batch job panicked"))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{execute, parse_machine};
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_spawn() {
+        // echoes inputs until it reads a 0
+        let (_, machine) = parse_machine("3,11,1006,11,10,4,11,1105,1,0,99,0").unwrap();
+        let (to_first, first_inputs) = mpsc::channel();
+        let (to_second, second_inputs) = mpsc::channel();
+        let (to_main, outputs) = mpsc::channel();
+
+        let first = spawn(machine.fork(), first_inputs, vec![to_second]);
+        let second = spawn(machine, second_inputs, vec![to_main]);
+        for value in [1, 2, 3] {
+            to_first.send(value).unwrap();
+        }
+        assert_eq!(outputs.recv(), Ok(1));
+
+        // hanging up on the first machine shuts the whole chain down
+        drop(to_first);
+        let (first, state) = first.join().unwrap();
+        assert_eq!(state, Ok(State::WaitingForInput));
+        assert_eq!(first.outputs, [1, 2, 3]);
+        let (_, state) = second.join().unwrap();
+        assert_eq!(state, Ok(State::WaitingForInput));
+        assert_eq!(outputs.iter().collect::<Vec<_>>(), [2, 3]);
+    }
+
+    #[test]
+    fn test_run_batch() {
+        // adds one to the value in the last cell
+        let (_, machine) = parse_machine("1001,5,1,5,99,0").unwrap();
+        let parameters = (0..100).collect::<Vec<i64>>();
+        let results = run_batch(&machine, &parameters, |mut machine, value| {
+            machine.program[5] = *value;
+            execute(&mut machine).ok()?;
+            Some(machine.program[5])
+        });
+        assert_eq!(results, (0..100).map(|v| Some(v + 1)).collect::<Vec<_>>());
+    }
+}
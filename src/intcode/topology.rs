@@ -0,0 +1,179 @@
+use std::{mem, ops::ControlFlow, sync::mpsc};
+
+use super::{spawn, IntcodeMachine, NetworkError, Outcome, Scheduler, State};
+
+/// A fixed graph of machines, each node's outputs feeding the inputs of the nodes it's
+/// connected to. Series pipelines are a chain of connections, feedback loops connect the
+/// last node back to the first.
+#[derive(Debug, Clone, Default)]
+pub struct Topology {
+    machines: Vec<IntcodeMachine>,
+    links: Vec<Vec<usize>>,
+    outputs: Vec<Vec<i64>>,
+}
+
+impl Topology {
+    pub fn new() -> Self {
+        Topology::default()
+    }
+
+    /// Adds a machine as a new node, returning its index.
+    pub fn add(&mut self, machine: IntcodeMachine) -> usize {
+        self.machines.push(machine);
+        self.links.push(Vec::new());
+        self.outputs.push(Vec::new());
+        self.machines.len() - 1
+    }
+
+    /// Sends everything `from` outputs to `to` as input. A node connected to several
+    /// others sends each of them every value.
+    pub fn connect(&mut self, from: usize, to: usize) -> &mut Self {
+        self.links[from].push(to);
+        self
+    }
+
+    /// Queues inputs for a node ahead of anything it's sent by other nodes.
+    pub fn seed(&mut self, node: usize, inputs: impl IntoIterator<Item = i64>) -> &mut Self {
+        self.machines[node].inputs.extend(inputs);
+        self
+    }
+
+    pub fn machine(&self, node: usize) -> &IntcodeMachine {
+        &self.machines[node]
+    }
+
+    /// Everything a node has output so far, whether or not it was passed on.
+    pub fn outputs(&self, node: usize) -> &[i64] {
+        &self.outputs[node]
+    }
+
+    /// Runs the graph until every node halts, or until none of them can make progress, in
+    /// which case the nodes left waiting for input are reported as deadlocked.
+    pub fn run(&mut self) -> Result<Outcome, NetworkError> {
+        let mut scheduler = Scheduler::new(mem::take(&mut self.machines));
+        let (links, outputs) = (&self.links, &mut self.outputs);
+
+        let outcome = scheduler.run(|from, values, machines| {
+            for to in links[from].iter() {
+                machines[*to].inputs.extend(values.iter());
+            }
+            outputs[from].extend(values);
+            ControlFlow::Continue(())
+        });
+
+        self.machines = scheduler.machines;
+        outcome
+    }
+
+    /// Runs every node on its own thread, connected by channels, and returns where each
+    /// one stopped. A node stops once it halts, or once it's waiting for input and every
+    /// node feeding it has stopped. Unlike [`run`](Topology::run), this can't detect a
+    /// cycle of nodes waiting on each other, which blocks forever.
+    pub fn run_threaded(&mut self) -> Result<Vec<State>, NetworkError> {
+        let (senders, receivers): (Vec<_>, Vec<_>) =
+            self.machines.iter().map(|_| mpsc::channel()).unzip();
+
+        let threads = mem::take(&mut self.machines)
+            .into_iter()
+            .zip(receivers)
+            .zip(self.links.iter())
+            .map(|((machine, receiver), links)| {
+                let senders = links.iter().map(|to| senders[*to].clone()).collect();
+                spawn(machine, receiver, senders)
+            })
+            .collect::<Vec<_>>();
+        // only the nodes themselves may keep each other's inputs open
+        drop(senders);
+
+        let mut states = Vec::with_capacity(threads.len());
+        let mut fault = None;
+        for (node, thread) in threads.into_iter().enumerate() {
+            let (mut machine, result) = thread.join().expect("machine thread panicked");
+            self.outputs[node].extend(machine.outputs.drain(..));
+            self.machines.push(machine);
+            match result {
+                Ok(state) => states.push(state),
+                Err(error) => {
+                    fault.get_or_insert(NetworkError { node, error });
+                }
+            }
+        }
+
+        match fault {
+            Some(fault) => Err(fault),
+            None => Ok(states),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{assemble, parse_machine};
+
+    #[test]
+    fn test_feedback_loop() {
+        // passes on its input plus one until it sees a 5, which it passes on before halting
+        let program = assemble(
+            "
+            loop:   IN [x]
+                    LT [x], #5, [go]
+                    JF [go], #done
+                    ADD [x], #1, [x]
+                    OUT [x]
+                    JT #1, #loop
+            done:   OUT [x]
+                    HLT
+            x:      .data 0
+            go:     .data 0
+            ",
+        )
+        .unwrap();
+        let machine = IntcodeMachine::from_program(program);
+        let mut topology = Topology::new();
+        for _ in 0..3 {
+            topology.add(machine.fork());
+        }
+        topology
+            .connect(0, 1)
+            .connect(1, 2)
+            .connect(2, 0)
+            .seed(0, [0]);
+
+        assert_eq!(topology.run(), Ok(Outcome::Halted));
+        assert_eq!(topology.outputs(0), [1, 4, 5]);
+        assert_eq!(topology.outputs(1), [2, 5, 5]);
+        assert_eq!(topology.outputs(2), [3, 5]);
+    }
+
+    #[test]
+    fn test_threaded() {
+        // doubles its input
+        let (_, double) = parse_machine("3,9,102,2,9,9,4,9,99").unwrap();
+        let mut topology = Topology::new();
+        for _ in 0..4 {
+            let node = topology.add(double.fork());
+            if node > 0 {
+                topology.connect(node - 1, node);
+            }
+        }
+        topology.seed(0, [3]);
+
+        assert_eq!(topology.run_threaded(), Ok(vec![State::Terminated; 4]));
+        assert_eq!(topology.outputs(3), [48]);
+    }
+
+    #[test]
+    fn test_deadlock() {
+        // waits for an input, then echoes it
+        let (_, echo) = parse_machine("3,9,4,9,99").unwrap();
+        let mut topology = Topology::new();
+        let source = topology.add(echo.clone());
+        let sink = topology.add(echo.clone());
+        let stuck = topology.add(echo);
+        topology.connect(source, sink).seed(source, [1]);
+
+        assert_eq!(topology.run(), Ok(Outcome::Deadlocked(vec![stuck])));
+        assert_eq!(topology.outputs(sink), [1]);
+    }
+}
@@ -0,0 +1,445 @@
+use itertools::Itertools;
+
+use std::{
+    fmt::{self, Display},
+    io::{self, Read, Write},
+};
+
+use super::{
+    fetch, opcode_from_number, operand, resolve, step, IntcodeError, IntcodeMachine, Opcode, State,
+    Stepper,
+};
+
+const MAGIC: &[u8; 8] = b"ICTRACE1";
+
+const HAS_WRITE: u8 = 1;
+const HAS_RELATIVE_BASE: u8 = 1 << 1;
+const HAS_INPUT: u8 = 1 << 2;
+const HAS_OUTPUT: u8 = 1 << 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryWrite {
+    pub address: usize,
+    pub old: i64,
+    pub new: i64,
+}
+
+/// Everything a single executed instruction did, with enough detail to both redo and
+/// undo it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub ip: usize,
+    pub opcode: Opcode,
+    /// Resolved operands: the value read for inputs, the address for the written operand.
+    pub operands: Vec<i64>,
+    pub write: Option<MemoryWrite>,
+    pub relative_base: Option<(i64, i64)>,
+    pub input: Option<i64>,
+    pub output: Option<i64>,
+    pub next_ip: usize,
+}
+
+impl Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}: {} {}",
+            self.ip,
+            self.opcode.mnemonic(),
+            self.operands.iter().join(" ")
+        )?;
+        if let Some(write) = self.write {
+            write!(f, "; [{}] {} -> {}", write.address, write.old, write.new)?;
+        }
+        if let Some((old, new)) = self.relative_base {
+            write!(f, "; rb {old} -> {new}")?;
+        }
+        if let Some(input) = self.input {
+            write!(f, "; in {input}")?;
+        }
+        if let Some(output) = self.output {
+            write!(f, "; out {output}")?;
+        }
+        Ok(())
+    }
+}
+
+fn peek(machine: &IntcodeMachine, address: usize) -> i64 {
+    machine.program.read(address)
+}
+
+/// Resolves the operands of the instruction at the instruction pointer, along with the
+/// address it's going to write to. Returns `None` if the instruction would fault.
+fn inspect(machine: &IntcodeMachine) -> Option<(Opcode, Vec<i64>, Option<usize>)> {
+    let instruction = fetch(machine).ok()?;
+    let written = instruction.opcode.written_parameter();
+    let mut operands = Vec::with_capacity(instruction.opcode.length() - 1);
+    let mut write_address = None;
+
+    for n in 0..instruction.opcode.length() - 1 {
+        if written == Some(n + 1) {
+            let address = resolve(machine, &instruction, n).ok()?;
+            operands.push(address as i64);
+            write_address = Some(address);
+        } else {
+            operands.push(operand(machine, &instruction, n).ok()?);
+        }
+    }
+
+    Some((instruction.opcode, operands, write_address))
+}
+
+/// Records every instruction executed through it. Use its [`Stepper::execute`] in place
+/// of [`execute`](super::execute) to opt in.
+#[derive(Debug, Clone, Default)]
+pub struct Tracer {
+    pub entries: Vec<TraceEntry>,
+}
+
+impl Tracer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Stepper for Tracer {
+    /// Executes and records a single instruction. Blocking on input records nothing, since
+    /// the machine doesn't change.
+    fn step(&mut self, machine: &mut IntcodeMachine) -> Result<State, IntcodeError> {
+        let Some((opcode, operands, write_address)) = inspect(machine) else {
+            // let the VM produce the fault
+            return step(machine);
+        };
+        let ip = machine.instruction_pointer;
+        let relative_base = machine.relative_base;
+        let input = machine.inputs.front().copied();
+        let old = write_address.map(|address| peek(machine, address));
+        let outputs = machine.outputs.len();
+
+        let state = step(machine)?;
+        if state == State::WaitingForInput {
+            return Ok(state);
+        }
+
+        self.entries.push(TraceEntry {
+            ip,
+            opcode,
+            operands,
+            write: write_address.zip(old).map(|(address, old)| MemoryWrite {
+                address,
+                old,
+                new: peek(machine, address),
+            }),
+            relative_base: (machine.relative_base != relative_base)
+                .then_some((relative_base, machine.relative_base)),
+            input: input.filter(|_| opcode == Opcode::Input),
+            output: (machine.outputs.len() > outputs)
+                .then(|| machine.outputs.back().copied())
+                .flatten(),
+            next_ip: machine.instruction_pointer,
+        });
+
+        Ok(state)
+    }
+}
+
+/// Writes a trace in a compact little-endian binary format.
+pub fn write_trace(entries: &[TraceEntry], mut writer: impl Write) -> io::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&(entries.len() as u64).to_le_bytes())?;
+
+    for entry in entries {
+        let flags = [
+            (entry.write.is_some(), HAS_WRITE),
+            (entry.relative_base.is_some(), HAS_RELATIVE_BASE),
+            (entry.input.is_some(), HAS_INPUT),
+            (entry.output.is_some(), HAS_OUTPUT),
+        ]
+        .iter()
+        .filter(|(present, _)| *present)
+        .fold(0, |flags, (_, flag)| flags | flag);
+
+        writer.write_all(&(entry.ip as u64).to_le_bytes())?;
+        writer.write_all(&(entry.next_ip as u64).to_le_bytes())?;
+        writer.write_all(&[
+            entry.opcode.number() as u8,
+            flags,
+            entry.operands.len() as u8,
+        ])?;
+        for operand in entry.operands.iter() {
+            writer.write_all(&operand.to_le_bytes())?;
+        }
+        if let Some(write) = entry.write {
+            writer.write_all(&(write.address as u64).to_le_bytes())?;
+            writer.write_all(&write.old.to_le_bytes())?;
+            writer.write_all(&write.new.to_le_bytes())?;
+        }
+        if let Some((old, new)) = entry.relative_base {
+            writer.write_all(&old.to_le_bytes())?;
+            writer.write_all(&new.to_le_bytes())?;
+        }
+        for value in entry.input.iter().chain(entry.output.iter()) {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+    }
+
+    Ok(())
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+struct TraceReader<R> {
+    reader: R,
+}
+
+impl<R: Read> TraceReader<R> {
+    fn bytes<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut bytes = [0; N];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn word(&mut self) -> io::Result<i64> {
+        Ok(i64::from_le_bytes(self.bytes()?))
+    }
+
+    fn address(&mut self) -> io::Result<usize> {
+        usize::try_from(u64::from_le_bytes(self.bytes()?))
+            .map_err(|_| invalid("address out of range"))
+    }
+
+    fn optional<T>(
+        &mut self,
+        present: bool,
+        read: impl FnOnce(&mut Self) -> io::Result<T>,
+    ) -> io::Result<Option<T>> {
+        present.then(|| read(self)).transpose()
+    }
+
+    fn entry(&mut self) -> io::Result<TraceEntry> {
+        let ip = self.address()?;
+        let next_ip = self.address()?;
+        let [opcode, flags, operands] = self.bytes()?;
+        let opcode = opcode_from_number(&(opcode as i64))
+            .ok_or_else(|| invalid("unknown opcode in trace"))?;
+        let operands = (0..operands)
+            .map(|_| self.word())
+            .collect::<io::Result<_>>()?;
+
+        Ok(TraceEntry {
+            ip,
+            opcode,
+            operands,
+            write: self.optional(flags & HAS_WRITE != 0, |r| {
+                Ok(MemoryWrite {
+                    address: r.address()?,
+                    old: r.word()?,
+                    new: r.word()?,
+                })
+            })?,
+            relative_base: self.optional(flags & HAS_RELATIVE_BASE != 0, |r| {
+                Ok((r.word()?, r.word()?))
+            })?,
+            input: self.optional(flags & HAS_INPUT != 0, Self::word)?,
+            output: self.optional(flags & HAS_OUTPUT != 0, Self::word)?,
+            next_ip,
+        })
+    }
+}
+
+/// Reads a trace written by [`write_trace`].
+pub fn read_trace(reader: impl Read) -> io::Result<Vec<TraceEntry>> {
+    let mut reader = TraceReader { reader };
+    if &reader.bytes::<8>()? != MAGIC {
+        return Err(invalid("not an Intcode trace"));
+    }
+
+    let count = reader.address()?;
+    (0..count).map(|_| reader.entry()).collect()
+}
+
+/// Returns the index of the first step at which two traces differ, e.g. the first branch
+/// that went another way because of a different input.
+pub fn divergence(a: &[TraceEntry], b: &[TraceEntry]) -> Option<usize> {
+    match a.iter().zip(b).position(|(a, b)| a != b) {
+        Some(step) => Some(step),
+        None if a.len() != b.len() => Some(a.len().min(b.len())),
+        None => None,
+    }
+}
+
+/// Moves a machine backwards and forwards through a recorded trace without re-executing
+/// it. The input queue holds the inputs the trace consumed.
+#[derive(Debug, Clone)]
+pub struct Replayer {
+    pub machine: IntcodeMachine,
+    entries: Vec<TraceEntry>,
+    position: usize,
+}
+
+impl Replayer {
+    /// Starts a replay from the machine the trace was recorded on, before it ran.
+    pub fn new(mut machine: IntcodeMachine, entries: Vec<TraceEntry>) -> Self {
+        machine.inputs = entries.iter().filter_map(|entry| entry.input).collect();
+        Replayer {
+            machine,
+            entries,
+            position: 0,
+        }
+    }
+
+    /// The number of steps replayed so far.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The entry that will be applied next, if any.
+    pub fn current(&self) -> Option<&TraceEntry> {
+        self.entries.get(self.position)
+    }
+
+    fn redo(&mut self) {
+        let entry = &self.entries[self.position];
+        if let Some(write) = entry.write {
+            // the write succeeded when it was recorded
+            self.machine.program[write.address] = write.new;
+        }
+        if let Some((_, new)) = entry.relative_base {
+            self.machine.relative_base = new;
+        }
+        if entry.input.is_some() {
+            self.machine.inputs.pop_front();
+        }
+        if let Some(output) = entry.output {
+            self.machine.outputs.push_back(output);
+        }
+        self.machine.instruction_pointer = entry.next_ip;
+        self.position += 1;
+    }
+
+    fn undo(&mut self) {
+        self.position -= 1;
+        let entry = &self.entries[self.position];
+        if let Some(write) = entry.write {
+            self.machine.program[write.address] = write.old;
+        }
+        if let Some((old, _)) = entry.relative_base {
+            self.machine.relative_base = old;
+        }
+        if let Some(input) = entry.input {
+            self.machine.inputs.push_front(input);
+        }
+        if entry.output.is_some() {
+            self.machine.outputs.pop_back();
+        }
+        self.machine.instruction_pointer = entry.ip;
+    }
+
+    /// Moves to the state after `position` steps, clamped to the length of the trace.
+    pub fn seek(&mut self, position: usize) {
+        let position = position.min(self.entries.len());
+        while self.position < position {
+            self.redo();
+        }
+        while self.position > position {
+            self.undo();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_machine, template::read_file_part, template::Day};
+
+    fn day_5_trace(input: i64) -> (IntcodeMachine, Vec<TraceEntry>) {
+        // outputs 999, 1000 or 1001 depending on how the input compares to 8
+        let (_, machine) = parse_machine(&crate::template::read_file(
+            "examples",
+            Day::new(5).unwrap(),
+        ))
+        .unwrap();
+        let mut run = machine.clone();
+        run.inputs.push_back(input);
+        let mut tracer = Tracer::new();
+        assert_eq!(tracer.execute(&mut run), Ok(State::Terminated));
+        (machine, tracer.entries)
+    }
+
+    #[test]
+    fn test_record() {
+        let (_, entries) = day_5_trace(8);
+        let first = &entries[0];
+        assert_eq!(first.opcode, Opcode::Input);
+        assert_eq!(first.input, Some(8));
+        assert_eq!(
+            first.write,
+            Some(MemoryWrite {
+                address: 21,
+                old: 0,
+                new: 8
+            })
+        );
+        assert_eq!(
+            entries.iter().filter_map(|e| e.output).collect::<Vec<_>>(),
+            vec![1000]
+        );
+    }
+
+    #[test]
+    fn test_divergence() {
+        let (_, below) = day_5_trace(7);
+        let (_, equal) = day_5_trace(8);
+        let step = divergence(&below, &equal).unwrap();
+        // the input itself differs
+        assert_eq!(step, 0);
+        assert_eq!(divergence(&below, &below[..3]), Some(3));
+        assert_eq!(divergence(&below, &below), None);
+    }
+
+    #[test]
+    fn test_serialize() {
+        let (_, entries) = day_5_trace(9);
+        let mut bytes = Vec::new();
+        write_trace(&entries, &mut bytes).unwrap();
+        assert_eq!(read_trace(bytes.as_slice()).unwrap(), entries);
+        assert!(read_trace(&b"garbage!"[..]).is_err());
+    }
+
+    #[test]
+    fn test_replay() {
+        let (_, machine) =
+            parse_machine(&read_file_part("examples", Day::new(9).unwrap(), 1)).unwrap();
+        let mut run = machine.clone();
+        let mut tracer = Tracer::new();
+        tracer.execute(&mut run).unwrap();
+
+        let mut replayer = Replayer::new(machine.clone(), tracer.entries);
+        replayer.seek(usize::MAX);
+        assert_eq!(replayer.machine.program, run.program);
+        assert_eq!(replayer.machine.outputs, run.outputs);
+        assert_eq!(
+            replayer.machine.instruction_pointer,
+            run.instruction_pointer
+        );
+
+        replayer.seek(0);
+        assert_eq!(
+            &replayer.machine.program.to_vec()[..machine.program.len()],
+            machine.program.to_vec()
+        );
+        assert!(replayer.machine.outputs.is_empty());
+        replayer.seek(1);
+        assert_eq!(replayer.current().unwrap().opcode, Opcode::Output);
+    }
+}
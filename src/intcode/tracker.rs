@@ -0,0 +1,243 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display},
+};
+
+use super::{
+    decode_at, fetch, reachable_instructions, resolve, step, IntcodeError, IntcodeMachine, Mode,
+    State, Stepper,
+};
+
+/// How often a cell was executed as the start of an instruction, read as an operand and
+/// written to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CellStats {
+    pub executed: u64,
+    pub reads: u64,
+    pub writes: u64,
+}
+
+/// A write into code: a cell that has already been executed, or that static analysis of
+/// the loaded program found to be part of a reachable instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodeModification {
+    /// Instruction pointer of the writing instruction.
+    pub writer: usize,
+    pub target: usize,
+    pub old: i64,
+    pub new: i64,
+    /// Whether the target had already been executed, rather than only being expected to be.
+    pub executed: bool,
+}
+
+impl Display for CodeModification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}: wrote {} over {} at {:04}, {} code",
+            self.writer,
+            self.new,
+            self.old,
+            self.target,
+            if self.executed {
+                "executed"
+            } else {
+                "reachable"
+            }
+        )
+    }
+}
+
+/// Keeps per-address statistics and catches self-modifying code while running a machine.
+/// Use its [`Stepper::execute`] in place of [`execute`](super::execute) to opt in.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryTracker {
+    pub stats: HashMap<usize, CellStats>,
+    pub modifications: Vec<CodeModification>,
+    /// Print each modification to stderr as it happens.
+    pub log: bool,
+    executed: HashSet<usize>,
+    reachable: Vec<bool>,
+}
+
+/// Addresses the instruction at the instruction pointer reads from and writes to, along
+/// with its length. Returns `None` if it would fault.
+fn accesses(machine: &IntcodeMachine) -> Option<(usize, Vec<usize>, Option<usize>)> {
+    let instruction = fetch(machine).ok()?;
+    let length = instruction.opcode.length();
+    let written = instruction.opcode.written_parameter();
+    let mut reads = Vec::new();
+    let mut write = None;
+
+    for n in 0..length - 1 {
+        if written == Some(n + 1) {
+            write = Some(resolve(machine, &instruction, n).ok()?);
+        } else if !matches!(instruction.modes[n], Mode::Immediate) {
+            reads.push(resolve(machine, &instruction, n).ok()?);
+        }
+    }
+
+    Some((length, reads, write))
+}
+
+impl MemoryTracker {
+    /// Prepares to track the machine, working out which cells of its program are
+    /// reachable code.
+    pub fn new(machine: &IntcodeMachine) -> Self {
+        let program = machine.program.to_vec();
+        let mut reachable = vec![false; program.len()];
+        for (address, start) in reachable_instructions(&program).into_iter().enumerate() {
+            if start {
+                let (instruction, _) = decode_at(program.as_slice(), address).unwrap();
+                reachable[address..address + instruction.opcode.length()].fill(true);
+            }
+        }
+
+        MemoryTracker {
+            reachable,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_logging(mut self) -> Self {
+        self.log = true;
+        self
+    }
+
+    /// Whether the cell has been executed as part of an instruction so far.
+    pub fn is_executed(&self, address: usize) -> bool {
+        self.executed.contains(&address)
+    }
+
+    /// The `count` most executed addresses, most executed first.
+    pub fn hot_spots(&self, count: usize) -> Vec<(usize, CellStats)> {
+        let mut hot = self
+            .stats
+            .iter()
+            .filter(|(_, stats)| stats.executed > 0)
+            .map(|(address, stats)| (*address, *stats))
+            .collect::<Vec<_>>();
+        hot.sort_unstable_by_key(|(address, stats)| (u64::MAX - stats.executed, *address));
+        hot.truncate(count);
+        hot
+    }
+}
+
+impl Stepper for MemoryTracker {
+    /// Executes a single instruction, updating statistics. Blocking on input counts
+    /// nothing, since the machine doesn't change.
+    fn step(&mut self, machine: &mut IntcodeMachine) -> Result<State, IntcodeError> {
+        let Some((length, reads, write)) = accesses(machine) else {
+            return step(machine);
+        };
+        let ip = machine.instruction_pointer;
+        let old = write.map(|address| machine.program.read(address));
+
+        let state = step(machine)?;
+        if state == State::WaitingForInput {
+            return Ok(state);
+        }
+
+        self.stats.entry(ip).or_default().executed += 1;
+        self.executed.extend(ip..ip + length);
+        for address in reads {
+            self.stats.entry(address).or_default().reads += 1;
+        }
+        if let Some((target, old)) = write.zip(old) {
+            self.stats.entry(target).or_default().writes += 1;
+            let executed = self.executed.contains(&target);
+            if executed || self.reachable.get(target).is_some_and(|code| *code) {
+                let modification = CodeModification {
+                    writer: ip,
+                    target,
+                    old,
+                    new: machine.program.read(target),
+                    executed,
+                };
+                if self.log {
+                    eprintln!("self-modifying code: {modification}");
+                }
+                self.modifications.push(modification);
+            }
+        }
+
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::parse_machine;
+
+    #[test]
+    fn test_modifications() {
+        // day 2's example overwrites the output operand of its first instruction, then the
+        // first instruction itself
+        let (_, mut machine) = parse_machine("1,9,10,3,2,3,11,0,99,30,40,50").unwrap();
+        let mut tracker = MemoryTracker::new(&machine);
+        assert_eq!(tracker.execute(&mut machine), Ok(State::Terminated));
+
+        assert_eq!(
+            tracker.modifications,
+            [
+                CodeModification {
+                    writer: 0,
+                    target: 3,
+                    old: 3,
+                    new: 70,
+                    executed: true
+                },
+                CodeModification {
+                    writer: 4,
+                    target: 0,
+                    old: 1,
+                    new: 3500,
+                    executed: true
+                }
+            ]
+        );
+        assert_eq!(
+            tracker.stats[&9],
+            CellStats {
+                executed: 0,
+                reads: 1,
+                writes: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_hot_spots() {
+        // count down from 3
+        let (_, mut machine) = parse_machine("1101,3,0,12,1001,12,-1,12,1005,12,4,99,0").unwrap();
+        let mut tracker = MemoryTracker::new(&machine);
+        tracker.execute(&mut machine).unwrap();
+
+        assert!(tracker.modifications.is_empty());
+        assert_eq!(
+            tracker.hot_spots(2),
+            [
+                (
+                    4,
+                    CellStats {
+                        executed: 3,
+                        reads: 0,
+                        writes: 0
+                    }
+                ),
+                (
+                    8,
+                    CellStats {
+                        executed: 3,
+                        reads: 0,
+                        writes: 0
+                    }
+                )
+            ]
+        );
+        assert_eq!(tracker.stats[&12].reads, 6);
+        assert!(tracker.is_executed(10));
+        assert!(!tracker.is_executed(12));
+    }
+}
@@ -1,4 +1,4 @@
-use advent_of_code::template::commands::{all, download, read, scaffold, solve, time};
+use advent_of_code::template::commands::{all, download, intcode, read, scaffold, solve, time};
 use args::{parse, AppArguments};
 
 #[cfg(feature = "today")]
@@ -8,7 +8,7 @@ use std::process;
 
 mod args {
     use advent_of_code::template::Day;
-    use std::process;
+    use std::{path::PathBuf, process};
 
     pub enum AppArguments {
         Download {
@@ -35,10 +35,43 @@ mod args {
             day: Option<Day>,
             store: bool,
         },
+        IntcodeDebug {
+            program: PathBuf,
+        },
+        IntcodeCfg {
+            program: PathBuf,
+        },
+        IntcodeProfile {
+            program: PathBuf,
+            inputs: Vec<i64>,
+            folded: Option<PathBuf>,
+        },
+        IntcodeFuzz {
+            seed: Option<u64>,
+            runs: u64,
+        },
+        IntcodeRun {
+            program: PathBuf,
+            inputs: Vec<i64>,
+            ascii: Vec<String>,
+            stdin: bool,
+            text: bool,
+            patches: Vec<(usize, i64)>,
+            max_steps: Option<u64>,
+        },
         #[cfg(feature = "today")]
         Today,
     }
 
+    fn parse_patch(patch: &str) -> Result<(usize, i64), String> {
+        let invalid = || format!("expected a patch like `1=12`, got `{patch}`");
+        let (address, value) = patch.split_once('=').ok_or_else(invalid)?;
+        Ok((
+            address.trim().parse().map_err(|_| invalid())?,
+            value.trim().parse().map_err(|_| invalid())?,
+        ))
+    }
+
     pub fn parse() -> Result<AppArguments, Box<dyn std::error::Error>> {
         let mut args = pico_args::Arguments::from_env();
 
@@ -72,6 +105,40 @@ mod args {
                 submit: args.opt_value_from_str("--submit")?,
                 dhat: args.contains("--dhat"),
             },
+            Some("intcode") => match args.subcommand()?.as_deref() {
+                Some("debug") => AppArguments::IntcodeDebug {
+                    program: args.free_from_str()?,
+                },
+                Some("cfg") => AppArguments::IntcodeCfg {
+                    program: args.free_from_str()?,
+                },
+                Some("fuzz") => AppArguments::IntcodeFuzz {
+                    seed: args.opt_value_from_str("--seed")?,
+                    runs: args.opt_value_from_str("--runs")?.unwrap_or(10_000),
+                },
+                Some("profile") => AppArguments::IntcodeProfile {
+                    inputs: args.values_from_str("--input")?,
+                    folded: args.opt_value_from_str("--folded")?,
+                    program: args.free_from_str()?,
+                },
+                Some("run") => AppArguments::IntcodeRun {
+                    inputs: args.values_from_str("--input")?,
+                    ascii: args.values_from_str("--ascii")?,
+                    stdin: args.contains("--stdin"),
+                    text: args.contains("--text"),
+                    patches: args.values_from_fn("--patch", parse_patch)?,
+                    max_steps: args.opt_value_from_str("--max-steps")?,
+                    program: args.free_from_str()?,
+                },
+                Some(x) => {
+                    eprintln!("Unknown intcode command: {x}");
+                    process::exit(1);
+                }
+                None => {
+                    eprintln!("No intcode command specified.");
+                    process::exit(1);
+                }
+            },
             #[cfg(feature = "today")]
             Some("today") => AppArguments::Today,
             Some(x) => {
@@ -116,6 +183,23 @@ fn main() {
                 dhat,
                 submit,
             } => solve::handle(day, release, dhat, submit),
+            AppArguments::IntcodeDebug { program } => intcode::debug(&program),
+            AppArguments::IntcodeCfg { program } => intcode::cfg(&program),
+            AppArguments::IntcodeProfile {
+                program,
+                inputs,
+                folded,
+            } => intcode::profile(&program, &inputs, folded.as_deref()),
+            AppArguments::IntcodeFuzz { seed, runs } => intcode::fuzz(seed, runs),
+            AppArguments::IntcodeRun {
+                program,
+                inputs,
+                ascii,
+                stdin,
+                text,
+                patches,
+                max_steps,
+            } => intcode::run(&program, &inputs, &ascii, stdin, text, &patches, max_steps),
             #[cfg(feature = "today")]
             AppArguments::Today => {
                 match Day::today() {
@@ -0,0 +1,180 @@
+use std::{
+    fs,
+    io::{self, BufRead, Write},
+    path::Path,
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::intcode::{
+    check_invariants, execute_with_budget_and_io, format_program, parse_machine, AsciiIo, Budget,
+    ControlFlowGraph, Debugger, IntcodeMachine, NumberIo, Profiler, ProgramGenerator, State,
+    Stepper,
+};
+
+fn load(path: &Path) -> IntcodeMachine {
+    let program = match fs::read_to_string(path) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("could not read {}: {e}", path.display());
+            process::exit(1);
+        }
+    };
+
+    match parse_machine(program.trim()) {
+        Ok(("", machine)) => machine,
+        _ => {
+            eprintln!("{} is not a valid Intcode program", path.display());
+            process::exit(1);
+        }
+    }
+}
+
+pub fn debug(path: &Path) {
+    let mut debugger = Debugger::new(load(path));
+    if let Err(e) = debugger.repl(io::stdin().lock(), io::stdout()) {
+        eprintln!("debugger i/o failed: {e}");
+        process::exit(1);
+    }
+}
+
+pub fn cfg(path: &Path) {
+    let cfg = ControlFlowGraph::new(&load(path).program.to_vec());
+    print!("{}", cfg.to_dot());
+
+    for write in cfg.code_writes.iter() {
+        eprintln!(
+            "warning: instruction at {} writes into code at {}",
+            write.writer, write.target
+        );
+    }
+    for addresses in cfg.loops() {
+        let starts = addresses
+            .iter()
+            .map(|a| format!("{a:04}"))
+            .collect::<Vec<_>>();
+        eprintln!("loop through blocks {}", starts.join(", "));
+    }
+}
+
+pub fn profile(path: &Path, inputs: &[i64], folded: Option<&Path>) {
+    let mut machine = load(path);
+    machine.inputs.extend(inputs);
+    let mut profiler = Profiler::new();
+
+    match profiler.execute(&mut machine) {
+        Ok(State::WaitingForInput) => eprintln!("stopped waiting for input"),
+        Ok(_) => (),
+        Err(e) => eprintln!("stopped on a fault: {e}"),
+    }
+    print!("{profiler}");
+
+    if let Some(folded) = folded {
+        let written = fs::File::create(folded).and_then(|file| profiler.write_folded(file));
+        if let Err(e) = written {
+            eprintln!("could not write {}: {e}", folded.display());
+            process::exit(1);
+        }
+    }
+}
+
+pub fn fuzz(seed: Option<u64>, runs: u64) {
+    let seed = seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64)
+    });
+    println!("checking {runs} programs from seed {seed}");
+
+    let mut failures = 0;
+    for run in (0..runs).map(|n| seed.wrapping_add(n)) {
+        let mut generator = ProgramGenerator::new(run);
+        let program = generator.program();
+        let inputs = generator.inputs();
+        if let Err(violation) = check_invariants(&program, &inputs) {
+            failures += 1;
+            println!("seed {run}: {violation}");
+            println!("  program: {}", format_program(&program));
+            println!("  inputs: {inputs:?}");
+        }
+    }
+
+    println!("{failures} of {runs} programs broke an invariant");
+    if failures > 0 {
+        process::exit(1);
+    }
+}
+
+/// Runs the program with inputs from `--input` values, then `--ascii` lines, then stdin
+/// as the program asks for them: numbers, or lines of text with `--text`. Outputs are
+/// printed as they're produced. Exits with 0 once it halts, 2 if it blocks on input, 3 if
+/// it runs out of steps and 4 on a fault.
+pub fn run(
+    path: &Path,
+    inputs: &[i64],
+    ascii: &[String],
+    stdin: bool,
+    text: bool,
+    patches: &[(usize, i64)],
+    max_steps: Option<u64>,
+) {
+    let mut machine = match load(path).with_patches(patches) {
+        Ok(machine) => machine,
+        Err(e) => {
+            eprintln!(
+                "could not patch address {} past the memory limit of {} cells",
+                e.address, e.limit
+            );
+            process::exit(1);
+        }
+    };
+    machine.inputs.extend(inputs);
+    for line in ascii {
+        machine
+            .inputs
+            .extend(line.bytes().chain([b'\n']).map(i64::from));
+    }
+
+    let budget = Budget {
+        max_instructions: max_steps,
+        ..Budget::unlimited()
+    };
+    // without --stdin the machine blocks once the given inputs run out
+    let reader: Box<dyn BufRead> = if stdin {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(io::empty())
+    };
+    let (result, failed) = if text {
+        let mut io = AsciiIo::new(reader, io::stdout());
+        let result = execute_with_budget_and_io(&mut machine, &budget, &mut io);
+        (result, io.error().map(ToString::to_string))
+    } else {
+        let mut io = NumberIo::new(reader, io::stdout());
+        let result = execute_with_budget_and_io(&mut machine, &budget, &mut io);
+        (result, io.error().map(ToString::to_string))
+    };
+    // exiting skips flushing whatever is left of the last line
+    let _ = io::stdout().flush();
+    if let Some(e) = failed {
+        eprintln!("could not read stdin or write stdout: {e}");
+        process::exit(1);
+    }
+
+    let address = machine.instruction_pointer;
+    match result {
+        Ok(State::Terminated) => (),
+        Ok(State::BudgetExhausted(_)) => {
+            eprintln!("stopped at address {address} after running out of steps");
+            process::exit(3);
+        }
+        Ok(_) => {
+            eprintln!("blocked at address {address} waiting for input");
+            process::exit(2);
+        }
+        Err(e) => {
+            eprintln!("stopped on a fault: {e}");
+            process::exit(4);
+        }
+    }
+}